@@ -0,0 +1,39 @@
+const NUM_ARTICULATIONS: usize = 6;
+
+/// How a note is played, chosen per MIDI channel with keyswitches. The keyswitches are consecutive
+/// notes starting at the keyswitch base note, in the order of this enum.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Articulation {
+    Normal,
+    // Struck and immediately damped with the mallet
+    DeadStroke,
+    // Played with a hand resting on the bar
+    Muted,
+    // Always rolls, even when rolls are turned off
+    Roll,
+    // Struck at the edge, which brings out the higher modes
+    Rimshot,
+    // Touched at the centre, which damps the modes that move there
+    Harmonics,
+}
+
+const ARTICULATIONS: [Articulation; NUM_ARTICULATIONS] = [
+    Articulation::Normal,
+    Articulation::DeadStroke,
+    Articulation::Muted,
+    Articulation::Roll,
+    Articulation::Rimshot,
+    Articulation::Harmonics,
+];
+
+impl Articulation {
+    /// Returns the articulation selected by a note, or `None` if the note is outside of the
+    /// keyswitch range.
+    pub fn from_keyswitch(note: u8, base_note: i32) -> Option<Self> {
+        let index = note as i32 - base_note;
+        if index < 0 {
+            return None;
+        }
+        ARTICULATIONS.get(index as usize).copied()
+    }
+}
@@ -1,22 +1,52 @@
 use nih_plug::prelude::*;
 use std::sync::Arc;
 
+mod articulation;
 mod constants;
 mod modal_synth;
+mod mode_file;
+mod onset_detector;
 mod params;
+mod roll;
+mod sample_kit;
+mod transient_shaper;
 mod voice;
 mod voice_manager;
+mod wav;
 
+use articulation::Articulation;
 use constants::MAX_BLOCK_SIZE;
-use params::PockyplockyParams;
+use mode_file::ModeFileTask;
+use onset_detector::OnsetDetector;
+use params::{BreathSource, ModeSourceKind, PockyplockyParams};
+use sample_kit::SampleKitTask;
 use voice_manager::VoiceManager;
 
 use crate::params::ParamBuffers;
 
+// Controls the expression of the bow
+const MOD_WHEEL_CC: u8 = 1;
+
+// Controls a sustained breath
+const BREATH_CC: u8 = 2;
+
 struct Pockyplocky {
     params: Arc<PockyplockyParams>,
     param_buffers: ParamBuffers,
     voices: VoiceManager,
+    onset_detector: OnsetDetector,
+    // Velocity of an onset that starts a note at the start of the next block
+    pending_onset: Option<f32>,
+    // The sample kit that was last asked to be loaded
+    requested_sample_kit: Option<usize>,
+    // The mode file that was last asked to be loaded
+    requested_mode_file: Option<usize>,
+}
+
+/// Files are loaded from disk on the background thread.
+enum BackgroundTask {
+    SampleKit(SampleKitTask),
+    ModeFile(ModeFileTask),
 }
 
 impl Default for Pockyplocky {
@@ -25,11 +55,60 @@ impl Default for Pockyplocky {
         Self {
             params: params.clone(),
             param_buffers: ParamBuffers::new(params.clone()),
-            voices: VoiceManager::new(params),
+            voices: VoiceManager::new(params.clone()),
+            onset_detector: OnsetDetector::new(params),
+            pending_onset: None,
+            requested_sample_kit: None,
+            requested_mode_file: None,
         }
     }
 }
 
+impl Pockyplocky {
+    /// Starts a voice for a note, with the articulation of its channel. Returns the voice's slot.
+    fn start_note(
+        &mut self,
+        context: &mut impl ProcessContext<Self>,
+        timing: u32,
+        voice_id: Option<i32>,
+        channel: u8,
+        note: u8,
+        velocity: f32,
+    ) -> usize {
+        let articulation = self.voices.articulation(channel);
+        let expression = self.voices.expression(channel);
+        let breath = self.voices.breath(channel);
+        let s = self
+            .voices
+            .start_voice(context, timing, voice_id, channel, note);
+        let voice = &mut self.voices.voices_mut()[s];
+
+        voice.start(
+            voice.voice_id,
+            voice.channel,
+            voice.note,
+            voice.internal_voice_id,
+            velocity,
+            articulation,
+        );
+        voice.set_expression(expression);
+        voice.set_breath(breath);
+        s
+    }
+
+    fn start_onset_note(
+        &mut self,
+        context: &mut impl ProcessContext<Self>,
+        timing: u32,
+        velocity: f32,
+    ) {
+        let note = self.onset_detector.select_note(velocity);
+        let s = self.start_note(context, timing, None, 0, note, velocity);
+        // There won't be a note off for it, so it's released right away
+        self.voices.voices_mut()[s].release();
+    }
+}
+
 impl Plugin for Pockyplocky {
     const NAME: &'static str = "Pockyplocky";
     const VENDOR: &'static str = "Lily's Nonexistent Company";
@@ -43,16 +122,24 @@ impl Plugin for Pockyplocky {
         ..AudioIOLayout::const_default()
     }];
 
-    const MIDI_INPUT: MidiConfig = MidiConfig::Basic;
+    const MIDI_INPUT: MidiConfig = MidiConfig::MidiCCs;
     const SAMPLE_ACCURATE_AUTOMATION: bool = true;
 
     type SysExMessage = ();
-    type BackgroundTask = ();
+    type BackgroundTask = BackgroundTask;
 
     fn params(&self) -> Arc<dyn Params> {
         self.params.clone()
     }
 
+    fn task_executor(&mut self) -> TaskExecutor<Self> {
+        let params = self.params.clone();
+        Box::new(move |task| match task {
+            BackgroundTask::SampleKit(task) => sample_kit::run_task(&params, task),
+            BackgroundTask::ModeFile(task) => mode_file::run_task(&params, task),
+        })
+    }
+
     fn initialize(
         &mut self,
         _audio_io_layout: &AudioIOLayout,
@@ -61,11 +148,17 @@ impl Plugin for Pockyplocky {
     ) -> bool {
         let sample_rate = buffer_config.sample_rate;
         self.voices.set_sample_rate(sample_rate);
+        self.onset_detector.set_sample_rate(sample_rate);
+        // This also runs after a project is opened, which brings its own sample kit and mode file
+        self.requested_sample_kit = None;
+        self.requested_mode_file = None;
         true
     }
 
     fn reset(&mut self) {
         self.voices.reset();
+        self.onset_detector.reset();
+        self.pending_onset = None;
     }
 
     fn process(
@@ -77,7 +170,45 @@ impl Plugin for Pockyplocky {
         let num_samples = buffer.samples();
         let output = buffer.as_slice();
 
+        // Sample kits and mode files are loaded from disk, so that happens on the background thread.
+        // A project that was just opened keeps the ones it was saved with, by now their index may
+        // point at another folder or file.
+        let sample_kit = self.params.sample_kit_index.value() as usize;
+        if self.params.sample_enabled.value() && self.requested_sample_kit != Some(sample_kit) {
+            let is_restored = self.requested_sample_kit.is_none()
+                && !self
+                    .params
+                    .sample_kit
+                    .try_read()
+                    .is_ok_and(|kit| kit.is_none());
+            if !is_restored {
+                context
+                    .execute_background(BackgroundTask::SampleKit(SampleKitTask::Load(sample_kit)));
+            }
+            self.requested_sample_kit = Some(sample_kit);
+        }
+
+        let mode_file = self.params.mode_file_index.value() as usize;
+        if self.params.mode_source.value() == ModeSourceKind::UserFile
+            && self.requested_mode_file != Some(mode_file)
+        {
+            let is_restored = self.requested_mode_file.is_none()
+                && !self
+                    .params
+                    .mode_file
+                    .try_read()
+                    .is_ok_and(|mode_file| mode_file.is_none());
+            if !is_restored {
+                context.execute_background(BackgroundTask::ModeFile(ModeFileTask::Load(mode_file)));
+            }
+            self.requested_mode_file = Some(mode_file);
+        }
+
+        self.voices
+            .set_tempo(context.transport().tempo.map(|tempo| tempo as f32));
+
         let mut next_event = context.next_event();
+        let mut onset_position: usize = 0;
         let mut block_start: usize = 0;
         let mut block_end: usize = MAX_BLOCK_SIZE.min(num_samples);
 
@@ -93,18 +224,72 @@ impl Plugin for Pockyplocky {
                                 note,
                                 velocity,
                             } => {
-                                let s = self
-                                    .voices
-                                    .start_voice(context, timing, voice_id, channel, note);
-                                let voice = &mut self.voices.voices_mut()[s];
-
-                                voice.start(
-                                    voice.voice_id,
-                                    voice.channel,
-                                    voice.note,
-                                    voice.internal_voice_id,
-                                    velocity,
-                                );
+                                // Keyswitches only change the articulation of the channel
+                                let keyswitch = if self.params.keyswitches_enabled.value() {
+                                    Articulation::from_keyswitch(
+                                        note,
+                                        self.params.keyswitch_base_note.value(),
+                                    )
+                                } else {
+                                    None
+                                };
+                                if let Some(articulation) = keyswitch {
+                                    self.voices.set_articulation(channel, articulation);
+                                } else {
+                                    self.start_note(
+                                        context, timing, voice_id, channel, note, velocity,
+                                    );
+                                }
+                            }
+                            NoteEvent::NoteOff {
+                                voice_id,
+                                channel,
+                                note,
+                                ..
+                            } => {
+                                self.voices.release_voices(voice_id, channel, note);
+                            }
+                            NoteEvent::PolyPressure {
+                                voice_id,
+                                channel,
+                                note,
+                                pressure,
+                                ..
+                            } => {
+                                self.voices.set_pressure(voice_id, channel, note, pressure);
+                            }
+                            // The shake CC can be set to any CC, so it goes before the fixed ones
+                            NoteEvent::MidiCC {
+                                channel, cc, value, ..
+                            } if self.params.particles_enabled.value()
+                                && cc as i32 == self.params.shake_cc.value() =>
+                            {
+                                self.voices.shake(channel, value);
+                            }
+                            NoteEvent::MidiCC {
+                                channel,
+                                cc: MOD_WHEEL_CC,
+                                value,
+                                ..
+                            } => {
+                                self.voices.set_expression(channel, value);
+                            }
+                            NoteEvent::MidiCC {
+                                channel,
+                                cc: BREATH_CC,
+                                value,
+                                ..
+                            } if self.params.breath_source.value()
+                                == BreathSource::BreathController =>
+                            {
+                                self.voices.set_breath(channel, value);
+                            }
+                            NoteEvent::MidiChannelPressure {
+                                channel, pressure, ..
+                            } if self.params.breath_source.value()
+                                == BreathSource::ChannelPressure =>
+                            {
+                                self.voices.set_breath(channel, pressure);
                             }
                             NoteEvent::Choke {
                                 timing,
@@ -128,20 +313,67 @@ impl Plugin for Pockyplocky {
                 }
             }
 
-            output[0][block_start..block_end].fill(0.0);
-            output[1][block_start..block_end].fill(0.0);
+            // Onsets in the input start notes. The block is split at an onset, so its note starts on
+            // the right sample at the start of the next block.
+            if let Some(velocity) = self.pending_onset.take() {
+                self.start_onset_note(context, block_start as u32, velocity);
+            }
+            if self.params.onset_enabled.value() {
+                while onset_position < block_end {
+                    let position = onset_position;
+                    onset_position += 1;
+                    let sample = (output[0][position] + output[1][position]) * 0.5;
+                    if let Some(velocity) = self.onset_detector.process(sample) {
+                        if position == block_start {
+                            self.start_onset_note(context, position as u32, velocity);
+                        } else {
+                            self.pending_onset = Some(velocity);
+                            block_end = position;
+                            break;
+                        }
+                    }
+                }
+            }
 
             let block_len = block_end - block_start;
 
+            // The input has to be copied before the output overwrites it, the buffers are shared
+            let audio_input = self.params.audio_input_enabled.value();
+            let mut dry = [[0.0; MAX_BLOCK_SIZE]; 2];
+            if audio_input {
+                for (channel, dry_channel) in dry.iter_mut().enumerate() {
+                    dry_channel[..block_len]
+                        .copy_from_slice(&output[channel][block_start..block_end]);
+                }
+            }
+
+            output[0][block_start..block_end].fill(0.0);
+            output[1][block_start..block_end].fill(0.0);
+
             self.param_buffers.process_block(block_len);
 
+            let mut input = [0.0; MAX_BLOCK_SIZE];
+            if audio_input {
+                let input_gain_buffer = self.param_buffers.get_input_gain_buffer();
+                for i in 0..block_len {
+                    input[i] = (dry[0][i] + dry[1][i]) * 0.5 * input_gain_buffer[i];
+                }
+            }
+            let voice_input = audio_input.then_some(&input[..block_len]);
+
             // Process all voices
             for voice in self.voices.voices_mut() {
                 if !voice.active {
                     continue;
                 }
 
-                voice.process_block(block_start, block_len, &self.param_buffers, output);
+                voice.process_block(
+                    block_start,
+                    block_len,
+                    &self.param_buffers,
+                    voice_input,
+                    output,
+                );
 
                 // Check for voice termination
                 if voice.is_finished() {
@@ -155,6 +387,16 @@ impl Plugin for Pockyplocky {
                 }
             }
 
+            if audio_input {
+                let wet = self.params.dry_wet.value();
+                for (channel, dry_channel) in dry.iter().enumerate() {
+                    for i in 0..block_len {
+                        let sample = &mut output[channel][block_start + i];
+                        *sample = dry_channel[i] * (1.0 - wet) + *sample * wet;
+                    }
+                }
+            }
+
             block_start = block_end;
             block_end = (block_start + MAX_BLOCK_SIZE).min(num_samples);
         }
@@ -1,37 +1,78 @@
 use std::sync::Arc;
 
+mod bell;
+mod bow;
+mod breath_noise;
+mod buzz;
+mod click;
+mod contact;
+mod delay_line;
 pub mod envelope;
 mod exciter;
-mod modes;
+mod exciter_source;
+mod feedback;
+mod geometry;
+mod hann_mallet;
+mod impulse_strike;
+mod jet;
+mod mode_source;
+pub mod modes;
+mod noise;
+mod particles;
+mod pitch_envelope;
 mod resonator;
+mod sample_player;
+mod strike_pattern;
+mod tube;
 mod wave_folder;
 
 use crate::{
-    constants::DEFAULT_SAMPLE_RATE,
+    articulation::Articulation,
+    constants::{DEFAULT_SAMPLE_RATE, MAX_BLOCK_SIZE},
     modal_synth::{
-        exciter::Exciter, modes::ModeCalculator, resonator::ModalResonator, wave_folder::WaveFolder,
+        buzz::Buzz, click::Click, envelope::Envelope, exciter::Exciter, modes::ModeCalculator,
+        pitch_envelope::PitchEnvelope, resonator::ModalResonator, tube::TubeResonator,
+        wave_folder::WaveFolder,
     },
     params::{ParamBuffers, PockyplockyParams},
 };
 
+// How far the mod envelope moves the wave folder amount at a Mod Envelope Fold of 1.0, the whole
+// range of the amount
+const MOD_ENVELOPE_FOLD_RANGE: f32 = 4.0;
+
 pub struct ModalSynth {
     params: Arc<PockyplockyParams>,
     pub sample_rate: f32,
     pub calculator: ModeCalculator,
     pub resonator: ModalResonator,
     pub exciter: Exciter,
+    pub pitch_envelope: PitchEnvelope,
+    mod_envelope: Envelope,
+    pub tube: TubeResonator,
+    pub buzz: Buzz,
     pub wave_folder: WaveFolder,
+    click: Click,
+    frequency: f32,
+    bar_displacement: f32,
 }
 
 impl ModalSynth {
-    pub fn new(params: Arc<PockyplockyParams>) -> Self {
+    pub fn new(params: Arc<PockyplockyParams>, seed: u64) -> Self {
         Self {
             params: params.clone(),
             sample_rate: DEFAULT_SAMPLE_RATE,
             calculator: ModeCalculator::new(params.clone()),
             resonator: ModalResonator::new(),
-            exciter: Exciter::new(params.clone()),
+            exciter: Exciter::new(params.clone(), seed),
+            pitch_envelope: PitchEnvelope::new(),
+            mod_envelope: Envelope::new(),
+            tube: TubeResonator::new(),
+            buzz: Buzz::new(),
             wave_folder: WaveFolder::new(),
+            click: Click::new(),
+            frequency: 0.0,
+            bar_displacement: 0.0,
         }
     }
 
@@ -39,28 +80,128 @@ impl ModalSynth {
         self.sample_rate = sample_rate;
         self.resonator.set_sample_rate(sample_rate);
         self.exciter.set_sample_rate(sample_rate);
+        self.pitch_envelope.set_sample_rate(sample_rate);
+        self.mod_envelope.set_sample_rate(sample_rate);
+        self.tube.set_sample_rate(sample_rate);
+        self.buzz.set_sample_rate(sample_rate);
+        self.click.set_sample_rate(sample_rate);
     }
 
     pub fn reset(&mut self) {
         self.resonator.reset();
         self.exciter.reset();
+        self.pitch_envelope.reset();
+        self.mod_envelope.reset();
+        self.tube.reset();
+        self.buzz.reset();
+        self.click.reset();
         self.calculator.reset();
+        self.bar_displacement = 0.0;
     }
 
-    pub fn start(&mut self, frequency: f32, velocity: f32, decay: f32) -> f32 {
+    pub fn start(
+        &mut self,
+        frequency: f32,
+        velocity: f32,
+        decay: f32,
+        note: u8,
+        articulation: Articulation,
+    ) -> f32 {
         self.resonator.reset();
-        self.calculator.set_frequency(frequency, decay);
+        self.buzz.reset();
+        self.bar_displacement = 0.0;
+        self.frequency = frequency;
+        self.calculator
+            .set_frequency(frequency, velocity, decay, note, articulation);
         let modes = self.calculator.get_modes();
         self.resonator.set_modes(modes);
         let mut max_decay_time = 0.0;
         for mode in modes {
-            if mode.decay > max_decay_time {
-                max_decay_time = mode.decay;
+            if mode.amplitude > 0.0 && mode.ring_time() > max_decay_time {
+                max_decay_time = mode.ring_time();
             }
         }
 
-        self.exciter.start(frequency, velocity);
-        max_decay_time
+        // The tube keeps ringing after the bar has died out
+        if self.params.tube_enabled.value() {
+            max_decay_time += self.tube.start(
+                frequency,
+                self.params.tube_detune.value(),
+                self.params.tube_damping.value(),
+            );
+        }
+
+        self.resonator
+            .set_contact_position(self.params.bow_position.value());
+        if self.params.bow_enabled.value() {
+            self.exciter.start_bow();
+        }
+        // The jet and the feedback have to make up for the decay of the fundamental
+        let fundamental_decay = modes[0].decay.max(0.01);
+        if self.params.jet_enabled.value() {
+            self.exciter.start_jet(frequency, fundamental_decay);
+        }
+        if self.params.feedback_enabled.value() {
+            self.exciter.start_feedback(fundamental_decay);
+        }
+
+        self.pitch_envelope.start(
+            self.params.pitch_envelope_amount.value(),
+            self.params.pitch_envelope_time.value(),
+        );
+        self.set_mod_envelope();
+        self.mod_envelope.start();
+
+        // Later strikes of the strike pattern and the particles have to ring out too
+        max_decay_time + self.exciter.start(frequency, velocity)
+    }
+
+    /// Lifts the bow, stops blowing and fades out the feedback, the note rings out from here.
+    pub fn release(&mut self) {
+        self.exciter.release();
+        self.mod_envelope.release();
+    }
+
+    fn set_mod_envelope(&mut self) {
+        let params = &self.params;
+        let envelope = &mut self.mod_envelope;
+        envelope.set_delay_time(params.mod_envelope_delay.value());
+        envelope.set_attack_time(params.mod_envelope_attack.value());
+        envelope.set_attack_tension(params.mod_envelope_attack_tension.value());
+        envelope.set_hold_time(params.mod_envelope_hold.value());
+        envelope.set_decay_time(params.mod_envelope_decay.value());
+        envelope.set_decay_tension(params.mod_envelope_decay_tension.value());
+        envelope.set_sustain_level(params.mod_envelope_sustain.value());
+        envelope.set_release_time(params.mod_envelope_release.value());
+        envelope.set_release_tension(params.mod_envelope_release_tension.value());
+    }
+
+    pub fn set_expression(&mut self, expression: f32) {
+        self.exciter.set_expression(expression);
+    }
+
+    pub fn set_breath(&mut self, breath: f32) {
+        self.exciter.set_breath(breath);
+    }
+
+    pub fn shake(&mut self, strength: f32) {
+        self.exciter.shake(strength);
+    }
+
+    /// Strikes the note again while it's still ringing, for rolls.
+    pub fn strike(&mut self, velocity: f32) {
+        self.exciter.start(self.frequency, velocity);
+        self.restart_mod_envelope();
+    }
+
+    /// Starts the mod envelope over from where it is, or keeps it going when it's legato.
+    fn restart_mod_envelope(&mut self) {
+        self.set_mod_envelope();
+        if self.params.mod_envelope_legato.value() {
+            self.mod_envelope.legato();
+        } else {
+            self.mod_envelope.retrigger();
+        }
     }
 
     #[allow(clippy::needless_range_loop)]
@@ -69,24 +210,107 @@ impl ModalSynth {
         output: &mut [f32],
         block_len: usize,
         param_buffers: &ParamBuffers,
+        input: Option<&[f32]>,
     ) {
         let gain_buffer = param_buffers.get_gain_buffer();
         self.exciter.process_block(output, block_len, param_buffers);
 
+        // The click is the exciter and the physical mallet without the resonator
+        let click_level = self.params.click_level.value();
+        let mut click = [0.0; MAX_BLOCK_SIZE];
+        if click_level > 0.0 {
+            click[..block_len].copy_from_slice(&output[..block_len]);
+        }
+
+        // Audio input excites the resonator along with the exciter
+        if let Some(input) = input {
+            for i in 0..block_len {
+                output[i] += input[i];
+            }
+        }
+
+        self.resonator.set_tension(self.params.tension.value());
+        self.resonator
+            .set_shimmer(self.params.shimmer.value(), self.params.bloom_time.value());
+        // The mod envelope moves at block rate, like the pitch envelope
+        let modulation = if self.params.mod_envelope_enabled.value() {
+            self.mod_envelope
+                .process_block(block_len)
+                .last()
+                .copied()
+                .unwrap_or(0.0)
+        } else {
+            0.0
+        };
+        let mod_pitch = (modulation * self.params.mod_envelope_pitch.value() / 12.0).exp2();
+        self.resonator
+            .set_pitch(self.pitch_envelope.next_block(block_len) * mod_pitch);
+
+        // The physical mallet, the bow, the jet and the feedback react to the movement of the bar,
+        // so they have to be run in the same loop
+        let bowing = self.params.bow_enabled.value();
+        let blowing = self.params.jet_enabled.value();
+        let feeding_back = self.params.feedback_enabled.value();
+        self.click.set_tone(self.params.click_tone.value());
         for i in 0..block_len {
-            let filtered_noise = self.resonator.process(output[i]);
-            let voice_sample = filtered_noise;
-            output[i] = voice_sample;
+            let contact = self.exciter.process_contact(self.bar_displacement);
+            if click_level > 0.0 {
+                click[i] = self.click.process(click[i] + contact);
+            }
+            let bow = if bowing {
+                self.exciter.process_bow(self.resonator.contact_velocity())
+            } else {
+                0.0
+            };
+            let jet = if blowing {
+                self.exciter.process_jet(self.bar_displacement)
+            } else {
+                0.0
+            };
+            let feedback = if feeding_back {
+                self.exciter.process_feedback(self.bar_displacement)
+            } else {
+                0.0
+            };
+            self.bar_displacement = self
+                .resonator
+                .process_with_contact(output[i] + contact + jet + feedback, bow);
+            output[i] = self.bar_displacement;
+        }
+
+        if self.params.tube_enabled.value() {
+            let level = self.params.tube_level.value();
+            for i in 0..block_len {
+                output[i] += self.tube.process(output[i]) * level;
+            }
+        }
+
+        if self.params.buzz_enabled.value() {
+            self.buzz.set_params(
+                self.params.buzz_threshold.value(),
+                self.params.buzz_level.value(),
+                self.params.buzz_colour.value(),
+            );
+            for i in 0..block_len {
+                output[i] = self.buzz.process(output[i]);
+            }
         }
 
         if self.params.wave_folder_enabled.value() {
-            let amount = self.params.wave_folder_amount.value();
-            self.wave_folder.set_amount(amount);
+            let amount = self.params.wave_folder_amount.value()
+                + modulation * self.params.mod_envelope_fold.value() * MOD_ENVELOPE_FOLD_RANGE;
+            self.wave_folder.set_amount(amount.clamp(1.0, 5.0));
             for i in 0..block_len {
                 output[i] = self.wave_folder.process(output[i]);
             }
         }
 
+        if click_level > 0.0 {
+            for i in 0..block_len {
+                output[i] += click[i] * click_level;
+            }
+        }
+
         for i in 0..block_len {
             output[i] *= gain_buffer[i];
         }
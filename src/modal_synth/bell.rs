@@ -0,0 +1,129 @@
+use crate::{
+    modal_synth::modes::{Mode, NUM_MODES},
+    params::BellThird,
+};
+
+pub struct BellPartialTuning {
+    pub cents: f32,
+    pub level: f32,
+}
+
+/// How the bell founder tuned the bell. Every named partial can be moved away from its ideal
+/// ratio and made louder or softer. The upper partials share a single setting.
+pub struct BellTuning {
+    pub hum: BellPartialTuning,
+    pub prime: BellPartialTuning,
+    pub tierce: BellPartialTuning,
+    pub quint: BellPartialTuning,
+    pub nominal: BellPartialTuning,
+    pub upper: BellPartialTuning,
+    pub warble: f32, // Doublet split in Hz
+    pub third: BellThird,
+}
+
+struct BellPartial {
+    ratio: f32,
+    amplitude: f32,
+    decay_factor: f32,
+}
+
+const NUM_PARTIALS: usize = 8;
+
+// Ratios are relative to the prime, which is tuned to the note being played. Index 2 is the tierce,
+// its ratio depends on the type of third of the bell.
+const BELL_PARTIALS: [BellPartial; NUM_PARTIALS] = [
+    // Hum
+    BellPartial {
+        ratio: 0.5,
+        amplitude: 0.6,
+        decay_factor: 1.0,
+    },
+    // Prime
+    BellPartial {
+        ratio: 1.0,
+        amplitude: 0.5,
+        decay_factor: 0.7,
+    },
+    // Tierce
+    BellPartial {
+        ratio: 1.2,
+        amplitude: 0.8,
+        decay_factor: 0.6,
+    },
+    // Quint
+    BellPartial {
+        ratio: 1.5,
+        amplitude: 0.25,
+        decay_factor: 0.4,
+    },
+    // Nominal
+    BellPartial {
+        ratio: 2.0,
+        amplitude: 1.0,
+        decay_factor: 0.45,
+    },
+    // Deciem
+    BellPartial {
+        ratio: 2.5,
+        amplitude: 0.35,
+        decay_factor: 0.25,
+    },
+    // Duodeciem
+    BellPartial {
+        ratio: 3.0,
+        amplitude: 0.25,
+        decay_factor: 0.2,
+    },
+    // Double octave
+    BellPartial {
+        ratio: 4.0,
+        amplitude: 0.15,
+        decay_factor: 0.15,
+    },
+];
+
+const MAJOR_TIERCE_RATIO: f32 = 1.25;
+
+/// Builds the modes of a bell. Every partial is split into a doublet, two modes a few Hz apart,
+/// which beat against each other and make the bell warble. Modes beyond the doublets are silent.
+pub fn build_bell_modes(tuning: &BellTuning, fundamental: f32, decay: f32) -> [Mode; NUM_MODES] {
+    let partial_tunings = [
+        &tuning.hum,
+        &tuning.prime,
+        &tuning.tierce,
+        &tuning.quint,
+        &tuning.nominal,
+        &tuning.upper,
+        &tuning.upper,
+        &tuning.upper,
+    ];
+
+    std::array::from_fn(|i| {
+        let partial_index = i / 2;
+        if partial_index >= NUM_PARTIALS {
+            return Mode::silent();
+        }
+
+        let partial = &BELL_PARTIALS[partial_index];
+        let partial_tuning = partial_tunings[partial_index];
+
+        let ratio = if partial_index == 2 && tuning.third == BellThird::Major {
+            MAJOR_TIERCE_RATIO
+        } else {
+            partial.ratio
+        };
+
+        let frequency = fundamental * ratio * 2.0f32.powf(partial_tuning.cents / 1200.0);
+        let split = if i % 2 == 0 { -0.5 } else { 0.5 } * tuning.warble;
+
+        Mode {
+            frequency: frequency + split,
+            amplitude: partial.amplitude * partial_tuning.level * 0.5,
+            decay: partial.decay_factor * decay,
+            aftersound_decay: 0.0,
+            aftersound_level: 0.0,
+            beat_frequency: 0.0,
+            beat_depth: 0.0,
+        }
+    })
+}
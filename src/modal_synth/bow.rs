@@ -0,0 +1,91 @@
+use crate::constants::DEFAULT_SAMPLE_RATE;
+
+// Bow velocity at full Bow Velocity, relative to the velocity read back from the resonator
+const MAX_BOW_VELOCITY: f32 = 0.4;
+
+// Strength of the friction force at the default sample rate
+const BOW_GAIN: f32 = 1.0;
+
+// Time it takes to start and stop drawing the bow, in seconds
+const BOW_ATTACK_TIME: f32 = 0.05;
+const BOW_RELEASE_TIME: f32 = 0.1;
+
+/// A bow drawn across the bar. The bow hair sticks to the bar and drags it along until the bar
+/// pulls loose and slips back, over and over, which keeps the bar ringing for as long as the bow
+/// is drawn. The friction follows the bow table of the bowed string in the Synthesis ToolKit.
+pub struct Bow {
+    sample_rate: f32,
+    gain: f32,
+    velocity: f32,
+    target_velocity: f32,
+    slope: f32,
+    attack_coefficient: f32,
+    release_coefficient: f32,
+    drawing: bool,
+}
+
+impl Bow {
+    pub fn new() -> Self {
+        let mut bow = Self {
+            sample_rate: DEFAULT_SAMPLE_RATE,
+            gain: 0.0,
+            velocity: 0.0,
+            target_velocity: 0.0,
+            slope: 0.0,
+            attack_coefficient: 0.0,
+            release_coefficient: 0.0,
+            drawing: false,
+        };
+        bow.set_sample_rate(DEFAULT_SAMPLE_RATE);
+        bow
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+        // The force is added every sample, so it has to be scaled to sound the same at all rates
+        self.gain = BOW_GAIN * DEFAULT_SAMPLE_RATE / sample_rate;
+        self.attack_coefficient = 1.0 - (-1.0 / (BOW_ATTACK_TIME * sample_rate)).exp();
+        self.release_coefficient = 1.0 - (-1.0 / (BOW_RELEASE_TIME * sample_rate)).exp();
+    }
+
+    pub fn reset(&mut self) {
+        self.velocity = 0.0;
+        self.target_velocity = 0.0;
+        self.drawing = false;
+    }
+
+    pub fn start(&mut self) {
+        self.velocity = 0.0;
+        self.drawing = true;
+    }
+
+    /// Lifts the bow off the bar.
+    pub fn release(&mut self) {
+        self.drawing = false;
+    }
+
+    /// `pressure` and `velocity` go from 0.0 to 1.0.
+    pub fn set_params(&mut self, pressure: f32, velocity: f32) {
+        self.slope = 5.0 - 4.0 * pressure;
+        self.target_velocity = velocity * MAX_BOW_VELOCITY;
+    }
+
+    /// Returns the friction force on the bar, given how fast the bar moves under the bow.
+    pub fn process(&mut self, bar_velocity: f32) -> f32 {
+        let (target, coefficient) = if self.drawing {
+            (self.target_velocity, self.attack_coefficient)
+        } else {
+            (0.0, self.release_coefficient)
+        };
+        self.velocity += (target - self.velocity) * coefficient;
+
+        if !self.drawing && self.velocity < 1e-5 {
+            return 0.0;
+        }
+
+        let relative_velocity = self.velocity - bar_velocity;
+        let friction = (relative_velocity * self.slope).abs() + 0.75;
+        let friction = friction.powi(-4).min(1.0);
+        relative_velocity * friction * self.gain
+    }
+}
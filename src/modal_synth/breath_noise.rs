@@ -0,0 +1,147 @@
+use rand_pcg::Pcg32;
+use std::sync::Arc;
+
+use crate::{
+    constants::DEFAULT_SAMPLE_RATE,
+    modal_synth::{
+        exciter_source::{EnvelopeShape, ExciterSource, Strike},
+        noise::{BandPass, Noise},
+    },
+    params::{BreathMode, ParamBuffers, PockyplockyParams},
+};
+
+// Time it takes a sustained breath to follow the controller, in seconds
+const BREATH_SMOOTHING_TIME: f32 = 0.01;
+
+/// Breathy noise, shaped by the breath envelope or, in sustained mode, by the breath source for as
+/// long as the note is held.
+pub struct BreathNoise {
+    params: Arc<PockyplockyParams>,
+    sample_rate: f32,
+    noise: Noise,
+    filter: BandPass,
+    fundamental: f32,
+    velocity_sqrt: f32,
+    sustained: bool, // Whether the note was started in sustained mode
+    breathing: bool, // Until release, a sustained breath follows the breath source
+    breath: f32,     // Set by the breath source, the level of a sustained breath
+    breath_smoothed: f32,
+    breath_coefficient: f32,
+}
+
+impl BreathNoise {
+    /// Every breath should get its own `seed`, so they don't all play the same noise.
+    pub fn new(params: Arc<PockyplockyParams>, seed: u64) -> Self {
+        Self {
+            params,
+            sample_rate: DEFAULT_SAMPLE_RATE,
+            noise: Noise::new(Pcg32::new(12345, 67890 + seed)),
+            filter: BandPass::new(),
+            fundamental: 0.0,
+            velocity_sqrt: 0.0,
+            sustained: false,
+            breathing: false,
+            breath: 0.0,
+            breath_smoothed: 0.0,
+            breath_coefficient: breath_coefficient(DEFAULT_SAMPLE_RATE),
+        }
+    }
+
+    fn sustained(&self) -> bool {
+        self.params.breath_mode.value() == BreathMode::Sustained
+    }
+}
+
+impl ExciterSource for BreathNoise {
+    fn enabled(&self) -> bool {
+        self.params.breath_level.value() > 0.0
+    }
+
+    fn velocity_sensitivity(&self) -> f32 {
+        self.params.breath_velocity_sensitivity.value()
+    }
+
+    fn envelope(&self) -> Option<EnvelopeShape> {
+        if self.sustained() {
+            return None;
+        }
+        Some(EnvelopeShape {
+            attack_ms: self.params.breath_attack.value(),
+            attack_curve: self.params.breath_attack_shape.value(),
+            decay_ms: self.params.breath_decay.value(),
+            decay_curve: self.params.breath_decay_shape.value(),
+        })
+    }
+
+    fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+        self.breath_coefficient = breath_coefficient(sample_rate);
+    }
+
+    fn reset(&mut self) {
+        self.noise.reset();
+        self.filter.reset();
+        self.velocity_sqrt = 0.0;
+        self.sustained = false;
+        self.breathing = false;
+        self.breath_smoothed = 0.0;
+    }
+
+    fn start(&mut self, fundamental: f32, velocity: f32) -> f32 {
+        self.fundamental = fundamental;
+        self.velocity_sqrt = velocity.sqrt();
+        self.sustained = self.sustained();
+        self.breathing = self.sustained;
+        0.0
+    }
+
+    fn release(&mut self) {
+        self.breathing = false;
+    }
+
+    fn set_breath(&mut self, breath: f32) {
+        self.breath = breath;
+    }
+
+    fn process_block(
+        &mut self,
+        _strikes: &[Strike],
+        param_buffers: &ParamBuffers,
+        output: &mut [f32],
+    ) {
+        // Breath Level is smoothed, so it's applied here instead of as the level of the layer
+        let noise_level_buffer = param_buffers.get_noise_level_buffer();
+
+        let colour = self.params.breath_colour.value();
+        let filtered = self.params.breath_filter_enabled.value();
+        if filtered {
+            let offset = self.params.breath_filter_offset.value();
+            self.filter.set(
+                self.fundamental * 2.0f32.powf(offset / 12.0),
+                self.params.breath_filter_q.value(),
+                self.sample_rate,
+            );
+        }
+
+        for (i, sample) in output.iter_mut().enumerate() {
+            let mut noise = self.noise.next(colour);
+            if filtered {
+                noise = self.filter.process(noise);
+            }
+            // A sustained breath follows the breath source instead of the envelope, and fades out
+            // after release
+            let level = if self.sustained {
+                let target = if self.breathing { self.breath } else { 0.0 };
+                self.breath_smoothed += (target - self.breath_smoothed) * self.breath_coefficient;
+                self.breath_smoothed
+            } else {
+                1.0
+            };
+            *sample = noise * level * noise_level_buffer[i] * self.velocity_sqrt;
+        }
+    }
+}
+
+fn breath_coefficient(sample_rate: f32) -> f32 {
+    1.0 - (-1.0 / (BREATH_SMOOTHING_TIME * sample_rate)).exp()
+}
@@ -0,0 +1,100 @@
+use crate::constants::DEFAULT_SAMPLE_RATE;
+
+// Resonance frequency and damping time of the loose part while it's not touching the instrument
+const RATTLE_FREQUENCY: f32 = 150.0;
+const RATTLE_DAMPING_TIME: f32 = 0.02;
+
+// Impacts are velocity changes per sample, this brings them to roughly the level of the input at
+// the default sample rate
+const IMPACT_GAIN: f32 = 10.0;
+
+// Cutoff of the buzz filter when the colour is all the way down, and how many octaves the colour
+// can raise it by
+const MIN_COLOUR_FREQUENCY: f32 = 500.0;
+const COLOUR_OCTAVES: f32 = 5.0;
+
+/// A loose part rattling against the instrument, like the membrane of a balafon mirliton, the
+/// buzzer on a kalimba tine or snare wires. The part has some play, it only gets pushed along once
+/// the instrument moves further than the threshold away from it. It then keeps moving on its own
+/// and hits the instrument again later, so the buzz depends on what happened before and not just on
+/// the current input.
+pub struct Buzz {
+    sample_rate: f32,
+    threshold: f32,
+    level: f32,
+    colour_coefficient: f32,
+    spring: f32,
+    damping: f32,
+    impact_gain: f32,
+    position: f32,
+    velocity: f32, // Per sample
+    previous_input: f32,
+    filter_state: f32,
+}
+
+impl Buzz {
+    pub fn new() -> Self {
+        let mut buzz = Self {
+            sample_rate: DEFAULT_SAMPLE_RATE,
+            threshold: 0.0,
+            level: 0.0,
+            colour_coefficient: 0.0,
+            spring: 0.0,
+            damping: 0.0,
+            impact_gain: 0.0,
+            position: 0.0,
+            velocity: 0.0,
+            previous_input: 0.0,
+            filter_state: 0.0,
+        };
+        buzz.set_sample_rate(DEFAULT_SAMPLE_RATE);
+        buzz
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+        let omega = 2.0 * std::f32::consts::PI * RATTLE_FREQUENCY / sample_rate;
+        self.spring = omega * omega;
+        self.damping = 1.0 - (-1.0 / (RATTLE_DAMPING_TIME * sample_rate)).exp();
+        self.impact_gain = IMPACT_GAIN * sample_rate / DEFAULT_SAMPLE_RATE;
+    }
+
+    pub fn reset(&mut self) {
+        self.position = 0.0;
+        self.velocity = 0.0;
+        self.previous_input = 0.0;
+        self.filter_state = 0.0;
+    }
+
+    /// `colour` goes from 0.0 (dark) to 1.0 (bright).
+    pub fn set_params(&mut self, threshold: f32, level: f32, colour: f32) {
+        self.threshold = threshold;
+        self.level = level;
+        let cutoff =
+            (MIN_COLOUR_FREQUENCY * (colour * COLOUR_OCTAVES).exp2()).min(self.sample_rate * 0.45);
+        self.colour_coefficient =
+            1.0 - (-2.0 * std::f32::consts::PI * cutoff / self.sample_rate).exp();
+    }
+
+    pub fn process(&mut self, input: f32) -> f32 {
+        let input_velocity = input - self.previous_input;
+        self.previous_input = input;
+
+        // Free movement of the loose part
+        self.velocity -= self.spring * self.position + self.damping * self.velocity;
+        self.position += self.velocity;
+
+        // When the instrument moves too far away the part gets hit and is carried along with it
+        let gap = input - self.position;
+        let mut impact = 0.0;
+        if gap.abs() > self.threshold {
+            self.position = input - self.threshold.copysign(gap);
+            impact = input_velocity - self.velocity;
+            self.velocity = input_velocity;
+        }
+
+        self.filter_state +=
+            (impact * self.impact_gain - self.filter_state) * self.colour_coefficient;
+        input + self.filter_state * self.level
+    }
+}
@@ -0,0 +1,44 @@
+use crate::constants::DEFAULT_SAMPLE_RATE;
+
+// Cutoff of the tone filter when the tone is all the way down, and how many octaves the tone can
+// raise it by
+const MIN_TONE_FREQUENCY: f32 = 200.0;
+const TONE_OCTAVES: f32 = 7.0;
+
+/// The sound of the exciter itself, like the thock of a mallet hitting the bar, sent straight to
+/// the output through a low-pass filter.
+pub struct Click {
+    sample_rate: f32,
+    coefficient: f32,
+    state: f32,
+}
+
+impl Click {
+    pub fn new() -> Self {
+        Self {
+            sample_rate: DEFAULT_SAMPLE_RATE,
+            coefficient: 0.0,
+            state: 0.0,
+        }
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+    }
+
+    pub fn reset(&mut self) {
+        self.state = 0.0;
+    }
+
+    /// `tone` goes from 0.0 (dark) to 1.0 (bright).
+    pub fn set_tone(&mut self, tone: f32) {
+        let cutoff =
+            (MIN_TONE_FREQUENCY * (tone * TONE_OCTAVES).exp2()).min(self.sample_rate * 0.45);
+        self.coefficient = 1.0 - (-2.0 * std::f32::consts::PI * cutoff / self.sample_rate).exp();
+    }
+
+    pub fn process(&mut self, input: f32) -> f32 {
+        self.state += (input - self.state) * self.coefficient;
+        self.state
+    }
+}
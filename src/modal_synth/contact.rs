@@ -0,0 +1,140 @@
+use crate::{constants::DEFAULT_SAMPLE_RATE, params::MalletMaterial};
+
+// Converts the output of the resonator into a displacement of the bar under the mallet
+const BAR_COMPLIANCE: f32 = 1e-3;
+
+// Very stiff mallets would otherwise become unstable at low sample rates
+const MIN_CONTACT_SAMPLES: f32 = 4.0;
+
+// How long the mallet has to fly away from the bar before it can't bounce back on it anymore
+const CLEARANCE_TIME: f32 = 0.005;
+
+struct MalletPreset {
+    contact_time_ms: f32, // For a full velocity strike at medium hardness
+    exponent: f32, // 1.0 for a linear spring, higher for felt that gets stiffer when compressed
+    mass: f32,
+}
+
+const fn mallet_preset(material: MalletMaterial) -> MalletPreset {
+    match material {
+        MalletMaterial::Yarn => MalletPreset {
+            contact_time_ms: 3.0,
+            exponent: 2.5,
+            mass: 1.0,
+        },
+        MalletMaterial::Rubber => MalletPreset {
+            contact_time_ms: 1.2,
+            exponent: 1.5,
+            mass: 1.0,
+        },
+        MalletMaterial::Plastic => MalletPreset {
+            contact_time_ms: 0.5,
+            exponent: 1.5,
+            mass: 1.0,
+        },
+        MalletMaterial::Rosewood => MalletPreset {
+            contact_time_ms: 0.3,
+            exponent: 1.5,
+            mass: 1.0,
+        },
+        MalletMaterial::Brass => MalletPreset {
+            contact_time_ms: 0.1,
+            exponent: 1.5,
+            mass: 1.5,
+        },
+        MalletMaterial::Felt => MalletPreset {
+            contact_time_ms: 2.0,
+            exponent: 2.3,
+            mass: 1.0,
+        },
+        MalletMaterial::Superball => MalletPreset {
+            contact_time_ms: 1.5,
+            exponent: 1.2,
+            mass: 0.3,
+        },
+    }
+}
+
+/// A mallet that hits the bar following Hertz's law of contact: the force is the compression of
+/// the mallet head raised to some power. The mallet pushes the bar, and the bar pushes back, so
+/// how long the contact lasts depends on the velocity, and a mallet can bounce on the bar more than
+/// once.
+pub struct HertzMallet {
+    sample_rate: f32,
+    position: f32, // Towards the bar, the bar is at 0
+    velocity: f32,
+    stiffness: f32,
+    exponent: f32,
+    mass: f32,
+    active: bool,
+}
+
+impl HertzMallet {
+    pub fn new() -> Self {
+        Self {
+            sample_rate: DEFAULT_SAMPLE_RATE,
+            position: 0.0,
+            velocity: 0.0,
+            stiffness: 0.0,
+            exponent: 1.0,
+            mass: 1.0,
+            active: false,
+        }
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+    }
+
+    pub fn reset(&mut self) {
+        self.position = 0.0;
+        self.velocity = 0.0;
+        self.active = false;
+    }
+
+    /// Starts a strike, the mallet touches the bar at `speed`. Harder mallets have a shorter contact
+    /// time.
+    pub fn start(&mut self, speed: f32, material: MalletMaterial, hardness: f32) {
+        let preset = mallet_preset(material);
+        let contact_time = (preset.contact_time_ms * 0.001 * (1.5 - hardness))
+            .max(MIN_CONTACT_SAMPLES / self.sample_rate);
+
+        // Pick the stiffness that gives this contact time for a strike at full speed, where the
+        // mallet head is compressed by about v * T / pi
+        let compression = contact_time / std::f32::consts::PI;
+        self.stiffness = preset.mass * (std::f32::consts::PI / contact_time).powi(2)
+            / compression.powf(preset.exponent - 1.0);
+        self.exponent = preset.exponent;
+        self.mass = preset.mass;
+
+        self.position = 0.0;
+        self.velocity = speed;
+        self.active = speed > 0.0;
+    }
+
+    /// Advances the mallet by one sample, returns the force on the bar.
+    pub fn process(&mut self, bar_displacement: f32) -> f32 {
+        if !self.active {
+            return 0.0;
+        }
+
+        let dt = 1.0 / self.sample_rate;
+        let compression = self.position - bar_displacement * BAR_COMPLIANCE;
+        let force = if compression > 0.0 {
+            self.stiffness * compression.powf(self.exponent)
+        } else {
+            0.0
+        };
+
+        self.velocity -= force / self.mass * dt;
+        self.position += self.velocity * dt;
+
+        // Once the mallet is flying away from the bar and is well clear of it, it's done
+        if self.velocity < 0.0 && self.position < self.velocity * CLEARANCE_TIME {
+            self.active = false;
+        }
+
+        // Scaled so the total excitation doesn't depend on the mass of the mallet
+        force * dt / self.mass
+    }
+}
@@ -0,0 +1,46 @@
+/// A delay line that can be read at fractional delays, with linear interpolation.
+pub struct DelayLine {
+    buffer: Vec<f32>,
+    write_pos: usize,
+}
+
+impl DelayLine {
+    pub fn new() -> Self {
+        Self {
+            buffer: vec![0.0; 2],
+            write_pos: 0,
+        }
+    }
+
+    /// Makes room for delays of up to `max_delay` samples. This allocates, so it should only be
+    /// called when the sample rate changes.
+    pub fn set_max_delay(&mut self, max_delay: usize) {
+        self.buffer = vec![0.0; max_delay + 2];
+        self.write_pos = 0;
+    }
+
+    pub fn max_delay(&self) -> f32 {
+        (self.buffer.len() - 2) as f32
+    }
+
+    pub fn reset(&mut self) {
+        self.buffer.fill(0.0);
+        self.write_pos = 0;
+    }
+
+    pub fn write(&mut self, sample: f32) {
+        self.write_pos = (self.write_pos + 1) % self.buffer.len();
+        self.buffer[self.write_pos] = sample;
+    }
+
+    /// Reads the sample written `delay` samples ago, a delay of 0.0 gives the last written sample.
+    pub fn read(&self, delay: f32) -> f32 {
+        let delay = delay.clamp(0.0, self.max_delay());
+        let whole = delay as usize;
+        let fraction = delay - whole as f32;
+        let len = self.buffer.len();
+        let a = self.buffer[(self.write_pos + len - whole) % len];
+        let b = self.buffer[(self.write_pos + len - whole - 1) % len];
+        a + (b - a) * fraction
+    }
+}
@@ -1,8 +1,14 @@
 use crate::constants::{DEFAULT_SAMPLE_RATE, MAX_BLOCK_SIZE};
 use crate::params;
 
-const EPS: f32 = 1e-6;
+// How steep the curve of a segment is at a tension of 1.0 or -1.0. At this steepness an
+// exponential decay covers 80 dB before it lands on zero.
+const MAX_STEEPNESS: f32 = 9.21;
 
+// Below this steepness a segment is straight, which avoids dividing by almost zero
+const MIN_STEEPNESS: f32 = 1e-3;
+
+/// Named curves, for params that pick one rather than setting the tension.
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum EnvelopeCurve {
     Linear,
@@ -10,25 +16,145 @@ pub enum EnvelopeCurve {
     Exponential,
 }
 
+impl EnvelopeCurve {
+    /// Exponential segments move fast at first and slowly settle on their target, like a capacitor
+    /// charging. Logarithmic segments do the opposite, they start slowly and speed up.
+    pub fn tension(self) -> f32 {
+        match self {
+            EnvelopeCurve::Linear => 0.0,
+            EnvelopeCurve::Logarithmic => 1.0,
+            EnvelopeCurve::Exponential => -1.0,
+        }
+    }
+}
+
+impl From<params::BreathAttackCurve> for EnvelopeCurve {
+    fn from(curve: params::BreathAttackCurve) -> Self {
+        match curve {
+            params::BreathAttackCurve::Linear => EnvelopeCurve::Linear,
+            params::BreathAttackCurve::Logarithmic => EnvelopeCurve::Logarithmic,
+            params::BreathAttackCurve::Exponential => EnvelopeCurve::Exponential,
+        }
+    }
+}
+
+impl From<params::BreathDecayCurve> for EnvelopeCurve {
+    fn from(curve: params::BreathDecayCurve) -> Self {
+        match curve {
+            params::BreathDecayCurve::Linear => EnvelopeCurve::Linear,
+            params::BreathDecayCurve::Exponential => EnvelopeCurve::Exponential,
+            params::BreathDecayCurve::Logarithmic => EnvelopeCurve::Logarithmic,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum EnvelopeStage {
+    Delay,
     Attack,
+    Hold,
     Decay,
+    Sustain,
+    Release,
     Idle,
 }
 
+/// A curved move from one value to another over a number of samples. The curve follows
+/// (e^(k·t) - 1) / (e^k - 1), where t goes from 0 to 1 and k is the steepness, so e^(k·t) can be
+/// stepped with a single multiplication per sample.
+#[derive(Debug, Clone, Copy)]
+struct Segment {
+    start: f32,
+    target: f32,
+    steps_left: u32,
+    // Linear segments step the value directly, curved ones step e^(k·t)
+    step: f32,
+    growth: f32,
+    scale: f32,
+    curved: bool,
+}
+
+impl Segment {
+    fn idle(value: f32) -> Self {
+        Self {
+            start: value,
+            target: value,
+            steps_left: 0,
+            step: 0.0,
+            growth: 1.0,
+            scale: 0.0,
+            curved: false,
+        }
+    }
+
+    fn new(start: f32, target: f32, num_steps: u32, tension: f32) -> Self {
+        let steepness = tension.clamp(-1.0, 1.0) * MAX_STEEPNESS;
+        let num_steps = num_steps.max(1);
+        if steepness.abs() < MIN_STEEPNESS {
+            return Self {
+                start,
+                target,
+                steps_left: num_steps,
+                step: (target - start) / num_steps as f32,
+                growth: 1.0,
+                scale: 0.0,
+                curved: false,
+            };
+        }
+
+        Self {
+            start,
+            target,
+            steps_left: num_steps,
+            step: 1.0,
+            growth: (steepness / num_steps as f32).exp(),
+            scale: (target - start) / steepness.exp_m1(),
+            curved: true,
+        }
+    }
+
+    /// Moves one sample along, returns the new value. The last step lands exactly on the target.
+    fn next(&mut self, value: f32) -> f32 {
+        if self.steps_left == 0 {
+            return self.target;
+        }
+
+        self.steps_left -= 1;
+        if self.steps_left == 0 {
+            self.target
+        } else if self.curved {
+            self.step *= self.growth;
+            self.start + (self.step - 1.0) * self.scale
+        } else {
+            value + self.step
+        }
+    }
+
+    fn is_done(&self) -> bool {
+        self.steps_left == 0
+    }
+}
+
+/// An envelope with delay, attack, hold, decay, sustain and release stages. Attack, decay and
+/// release are curved with their own tension, from -1.0 (exponential) through 0.0 (linear) to 1.0
+/// (logarithmic). A sustain level of 0.0 makes it end after the decay, otherwise it stays at the
+/// sustain level until `release()`.
 pub struct Envelope {
     sample_rate: f32,
-    current_stage: EnvelopeStage,
-    current_value: f32,
-    target_value: f32,
-    steps_left: i32,
-    step_size: f32,
+    stage: EnvelopeStage,
+    value: f32,
+    segment: Segment,
+    released: bool,
 
+    delay_time_ms: f32,
     attack_time_ms: f32,
-    attack_curve: EnvelopeCurve,
+    attack_tension: f32,
+    hold_time_ms: f32,
     decay_time_ms: f32,
-    decay_curve: EnvelopeCurve,
+    decay_tension: f32,
+    sustain_level: f32,
+    release_time_ms: f32,
+    release_tension: f32,
 
     envelope_values: [f32; MAX_BLOCK_SIZE],
 }
@@ -37,15 +163,19 @@ impl Envelope {
     pub fn new() -> Self {
         Self {
             sample_rate: DEFAULT_SAMPLE_RATE,
-            current_stage: EnvelopeStage::Idle,
-            current_value: 0.0,
-            target_value: 0.0,
-            steps_left: 0,
-            step_size: 0.0,
+            stage: EnvelopeStage::Idle,
+            value: 0.0,
+            segment: Segment::idle(0.0),
+            released: false,
+            delay_time_ms: 0.0,
             attack_time_ms: 0.0,
-            attack_curve: EnvelopeCurve::Linear,
+            attack_tension: 0.0,
+            hold_time_ms: 0.0,
             decay_time_ms: 100.0,
-            decay_curve: EnvelopeCurve::Exponential,
+            decay_tension: EnvelopeCurve::Exponential.tension(),
+            sustain_level: 0.0,
+            release_time_ms: 0.0,
+            release_tension: EnvelopeCurve::Exponential.tension(),
             envelope_values: [0.0; MAX_BLOCK_SIZE],
         }
     }
@@ -55,110 +185,159 @@ impl Envelope {
     }
 
     pub fn reset(&mut self) {
-        self.current_stage = EnvelopeStage::Idle;
-        self.current_value = 0.0;
-        self.target_value = 0.0;
-        self.steps_left = 0;
-        self.step_size = 0.0;
+        self.stage = EnvelopeStage::Idle;
+        self.value = 0.0;
+        self.segment = Segment::idle(0.0);
+        self.released = false;
+    }
+
+    pub fn set_delay_time(&mut self, time_ms: f32) {
+        self.delay_time_ms = time_ms;
     }
 
     pub fn set_attack_time(&mut self, time_ms: f32) {
         self.attack_time_ms = time_ms;
     }
 
-    pub fn set_attack_curve(&mut self, curve: params::BreathAttackCurve) {
-        self.attack_curve = match curve {
-            params::BreathAttackCurve::Linear => EnvelopeCurve::Linear,
-            params::BreathAttackCurve::Logarithmic => EnvelopeCurve::Logarithmic,
-            params::BreathAttackCurve::Exponential => EnvelopeCurve::Exponential,
-        };
+    pub fn set_attack_tension(&mut self, tension: f32) {
+        self.attack_tension = tension;
+    }
+
+    pub fn set_attack_curve(&mut self, curve: impl Into<EnvelopeCurve>) {
+        self.attack_tension = curve.into().tension();
+    }
+
+    pub fn set_hold_time(&mut self, time_ms: f32) {
+        self.hold_time_ms = time_ms;
     }
 
     pub fn set_decay_time(&mut self, time_ms: f32) {
         self.decay_time_ms = time_ms;
     }
 
-    pub fn set_decay_curve(&mut self, curve: params::BreathDecayCurve) {
-        self.decay_curve = match curve {
-            params::BreathDecayCurve::Linear => EnvelopeCurve::Linear,
-            params::BreathDecayCurve::Exponential => EnvelopeCurve::Exponential,
-        };
+    pub fn set_decay_tension(&mut self, tension: f32) {
+        self.decay_tension = tension;
     }
 
-    fn num_steps(&self, time_ms: f32) -> u32 {
-        (self.sample_rate * time_ms * 0.001).round() as u32
+    pub fn set_decay_curve(&mut self, curve: impl Into<EnvelopeCurve>) {
+        self.decay_tension = curve.into().tension();
     }
 
-    fn step_size(&self, start: f32, target: f32, num_steps: u32, curve: EnvelopeCurve) -> f32 {
-        match curve {
-            EnvelopeCurve::Linear => (target - start) / num_steps as f32,
-            EnvelopeCurve::Logarithmic => {
-                ((target + EPS) / (start + EPS)).powf(1.0 / num_steps as f32)
-            }
-            EnvelopeCurve::Exponential => 0.0001f32.powf(1.0 / num_steps as f32),
-        }
+    /// The level the envelope stays at after the decay, from 0.0 to 1.0.
+    pub fn set_sustain_level(&mut self, level: f32) {
+        self.sustain_level = level.clamp(0.0, 1.0);
     }
 
-    fn next_value(&self, current: f32, target: f32, step_size: f32, curve: EnvelopeCurve) -> f32 {
-        match curve {
-            EnvelopeCurve::Linear => current + step_size,
-            EnvelopeCurve::Logarithmic => (current + EPS) * step_size - EPS,
-            EnvelopeCurve::Exponential => (current * step_size) + (target * (1.0 - step_size)),
-        }
+    pub fn set_release_time(&mut self, time_ms: f32) {
+        self.release_time_ms = time_ms;
+    }
+
+    pub fn set_release_tension(&mut self, tension: f32) {
+        self.release_tension = tension;
+    }
+
+    fn num_steps(&self, time_ms: f32) -> u32 {
+        (self.sample_rate * time_ms * 0.001).round() as u32
     }
 
+    /// Starts the envelope from silence.
     pub fn start(&mut self) {
-        self.current_stage = EnvelopeStage::Attack;
-        self.current_value = 0.0;
-        self.target_value = 1.0;
+        self.value = 0.0;
+        self.retrigger();
+    }
 
-        let num_steps = self.num_steps(self.attack_time_ms);
-        self.steps_left = num_steps as i32;
-        self.step_size = self.step_size(0.0, 1.0, num_steps, self.attack_curve);
+    /// Starts the envelope again from where it is, so a note played over a ringing one doesn't
+    /// click.
+    pub fn retrigger(&mut self) {
+        self.released = false;
+        self.enter(EnvelopeStage::Delay);
     }
 
-    fn process_sample(&mut self) -> f32 {
-        if self.steps_left > 0 {
-            let old_steps_left = self.steps_left;
-            self.steps_left -= 1;
-
-            let new_value = if old_steps_left == 1 {
-                self.target_value
-            } else {
-                let curve = match self.current_stage {
-                    EnvelopeStage::Attack => self.attack_curve,
-                    EnvelopeStage::Decay => self.decay_curve,
-                    EnvelopeStage::Idle => EnvelopeCurve::Linear,
-                };
-                self.next_value(self.current_value, self.target_value, self.step_size, curve)
-            };
+    /// Keeps the envelope going when it's still held, like a legato note on a monophonic synth.
+    /// Once it's released or has ended it's retriggered.
+    pub fn legato(&mut self) {
+        if self.released || self.stage == EnvelopeStage::Idle {
+            self.retrigger();
+        }
+    }
 
-            self.current_value = new_value;
-            new_value
-        } else {
-            match self.current_stage {
-                EnvelopeStage::Attack => {
-                    self.current_stage = EnvelopeStage::Decay;
-                    self.current_value = 1.0;
-                    self.target_value = 0.0;
+    /// Moves on to the release stage, from wherever the envelope is.
+    pub fn release(&mut self) {
+        if self.stage == EnvelopeStage::Idle {
+            return;
+        }
 
-                    let num_steps = self.num_steps(self.decay_time_ms);
-                    self.steps_left = num_steps as i32;
-                    self.step_size = self.step_size(1.0, 0.0, num_steps, self.decay_curve);
+        self.released = true;
+        self.enter(EnvelopeStage::Release);
+    }
 
-                    self.current_value
-                }
+    /// Enters a stage, skipping the ones that take no time.
+    fn enter(&mut self, stage: EnvelopeStage) {
+        let mut stage = stage;
+        loop {
+            let (time_ms, target, tension) = match stage {
+                EnvelopeStage::Delay => (self.delay_time_ms, self.value, 0.0),
+                EnvelopeStage::Attack => (self.attack_time_ms, 1.0, self.attack_tension),
+                EnvelopeStage::Hold => (self.hold_time_ms, 1.0, 0.0),
                 EnvelopeStage::Decay => {
-                    self.current_stage = EnvelopeStage::Idle;
-                    self.current_value = 0.0;
-                    self.steps_left = 0;
-                    self.current_value
+                    (self.decay_time_ms, self.sustain_level, self.decay_tension)
+                }
+                EnvelopeStage::Sustain => {
+                    if self.sustain_level <= 0.0 {
+                        stage = EnvelopeStage::Idle;
+                        continue;
+                    }
+                    self.value = self.sustain_level;
+                    self.segment = Segment::idle(self.value);
+                    break;
+                }
+                EnvelopeStage::Release => (self.release_time_ms, 0.0, self.release_tension),
+                EnvelopeStage::Idle => {
+                    self.value = 0.0;
+                    self.segment = Segment::idle(0.0);
+                    break;
                 }
-                EnvelopeStage::Idle => 0.0,
+            };
+
+            let num_steps = self.num_steps(time_ms);
+            if num_steps == 0 {
+                self.value = target;
+                stage = self.next_stage(stage);
+                continue;
             }
+
+            self.segment = Segment::new(self.value, target, num_steps, tension);
+            break;
+        }
+        self.stage = stage;
+    }
+
+    fn next_stage(&self, stage: EnvelopeStage) -> EnvelopeStage {
+        match stage {
+            EnvelopeStage::Delay => EnvelopeStage::Attack,
+            EnvelopeStage::Attack => EnvelopeStage::Hold,
+            EnvelopeStage::Hold => EnvelopeStage::Decay,
+            EnvelopeStage::Decay => EnvelopeStage::Sustain,
+            EnvelopeStage::Sustain => EnvelopeStage::Sustain,
+            EnvelopeStage::Release | EnvelopeStage::Idle => EnvelopeStage::Idle,
         }
     }
 
+    fn process_sample(&mut self) -> f32 {
+        match self.stage {
+            EnvelopeStage::Idle => return 0.0,
+            EnvelopeStage::Sustain => return self.value,
+            _ => {}
+        }
+
+        self.value = self.segment.next(self.value);
+        let value = self.value;
+        if self.segment.is_done() {
+            self.enter(self.next_stage(self.stage));
+        }
+        value
+    }
+
     pub fn process_block(&mut self, block_len: usize) -> &[f32] {
         for i in 0..block_len {
             self.envelope_values[i] = self.process_sample();
@@ -166,3 +345,205 @@ impl Envelope {
         &self.envelope_values[..block_len]
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // At this sample rate a millisecond is a sample
+    const SAMPLE_RATE: f32 = 1000.0;
+
+    fn envelope() -> Envelope {
+        let mut envelope = Envelope::new();
+        envelope.set_sample_rate(SAMPLE_RATE);
+        envelope.set_decay_time(0.0);
+        envelope
+    }
+
+    fn render(envelope: &mut Envelope, num_samples: usize) -> Vec<f32> {
+        let mut values = Vec::with_capacity(num_samples);
+        while values.len() < num_samples {
+            let block_len = (num_samples - values.len()).min(MAX_BLOCK_SIZE);
+            values.extend_from_slice(envelope.process_block(block_len));
+        }
+        values
+    }
+
+    fn assert_near(value: f32, expected: f32) {
+        assert!(
+            (value - expected).abs() < 1e-3,
+            "expected {expected}, got {value}"
+        );
+    }
+
+    #[test]
+    fn stages_take_their_time_and_end_on_their_target() {
+        let mut envelope = envelope();
+        envelope.set_delay_time(5.0);
+        envelope.set_attack_time(10.0);
+        envelope.set_hold_time(5.0);
+        envelope.set_decay_time(20.0);
+        envelope.set_sustain_level(0.5);
+        envelope.set_release_time(10.0);
+        envelope.start();
+
+        let values = render(&mut envelope, 100);
+        assert!(values[..5].iter().all(|&value| value == 0.0));
+        assert!(values[13] < 1.0);
+        assert_eq!(values[14], 1.0);
+        assert!(values[15..20].iter().all(|&value| value == 1.0));
+        assert!(values[38] > 0.5);
+        assert_eq!(values[39], 0.5);
+        assert!(values[40..].iter().all(|&value| value == 0.5));
+        assert_eq!(envelope.stage, EnvelopeStage::Sustain);
+
+        envelope.release();
+        let values = render(&mut envelope, 20);
+        assert!(values[8] > 0.0);
+        assert_eq!(values[9], 0.0);
+        assert_eq!(envelope.stage, EnvelopeStage::Idle);
+        assert!(values[10..].iter().all(|&value| value == 0.0));
+    }
+
+    #[test]
+    fn tension_shapes_the_curve() {
+        let decay_at = |configure: &dyn Fn(&mut Envelope)| {
+            let mut envelope = envelope();
+            envelope.set_decay_time(100.0);
+            configure(&mut envelope);
+            envelope.start();
+            let values = render(&mut envelope, 100);
+            [values[24], values[49], values[74], values[99]]
+        };
+
+        let linear = decay_at(&|envelope| envelope.set_decay_tension(0.0));
+        for (value, expected) in linear.iter().zip([0.75, 0.5, 0.25, 0.0]) {
+            assert_near(*value, expected);
+        }
+
+        // An exponential decay falls by 80 dB over its length, so 40 dB by the middle
+        let exponential = decay_at(&|envelope| envelope.set_decay_tension(-1.0));
+        for (value, expected) in exponential.iter().zip([0.1, 0.01, 0.001, 0.0]) {
+            assert_near(*value, expected);
+        }
+
+        let logarithmic =
+            decay_at(&|envelope| envelope.set_decay_curve(params::BreathDecayCurve::Logarithmic));
+        for (value, expected) in logarithmic.iter().zip([0.999, 0.99, 0.9, 0.0]) {
+            assert_near(*value, expected);
+        }
+
+        let mut envelope = envelope();
+        envelope.set_attack_time(100.0);
+        envelope.set_attack_curve(params::BreathAttackCurve::Exponential);
+        envelope.start();
+        let values = render(&mut envelope, 100);
+        assert_near(values[24], 0.9);
+        assert_near(values[49], 0.99);
+        assert_eq!(values[99], 1.0);
+
+        let mut envelope = self::envelope();
+        envelope.set_attack_time(100.0);
+        envelope.set_attack_curve(params::BreathAttackCurve::Linear);
+        envelope.start();
+        assert_near(render(&mut envelope, 50)[49], 0.5);
+    }
+
+    #[test]
+    fn sustain_of_zero_ends_after_the_decay() {
+        let mut envelope = envelope();
+        envelope.set_decay_time(10.0);
+        envelope.start();
+
+        let values = render(&mut envelope, 20);
+        assert_eq!(values[9], 0.0);
+        assert_eq!(envelope.stage, EnvelopeStage::Idle);
+        assert!(values[10..].iter().all(|&value| value == 0.0));
+    }
+
+    #[test]
+    fn sustain_holds_until_released() {
+        let mut envelope = envelope();
+        envelope.set_decay_time(10.0);
+        envelope.set_sustain_level(0.3);
+        envelope.start();
+
+        let values = render(&mut envelope, 1000);
+        assert!(values[9..].iter().all(|&value| value == 0.3));
+        assert_eq!(envelope.stage, EnvelopeStage::Sustain);
+
+        envelope.release();
+        assert_eq!(envelope.stage, EnvelopeStage::Idle);
+        assert_eq!(render(&mut envelope, 1)[0], 0.0);
+    }
+
+    #[test]
+    fn retrigger_starts_from_the_current_value() {
+        let mut envelope = envelope();
+        envelope.set_attack_time(10.0);
+        envelope.set_attack_tension(0.0);
+        envelope.set_decay_time(10.0);
+        envelope.set_decay_tension(0.0);
+        envelope.start();
+
+        let before = *render(&mut envelope, 15).last().unwrap();
+        assert_near(before, 0.5);
+        envelope.retrigger();
+        assert_eq!(envelope.stage, EnvelopeStage::Attack);
+
+        // The attack carries on from 0.5 in equal steps towards 1.0
+        let values = render(&mut envelope, 10);
+        assert_near(values[0], 0.55);
+        assert_eq!(values[9], 1.0);
+    }
+
+    #[test]
+    fn legato_keeps_going_while_held() {
+        let mut envelope = envelope();
+        envelope.set_attack_time(10.0);
+        envelope.set_attack_tension(0.0);
+        envelope.set_decay_time(10.0);
+        envelope.set_decay_tension(0.0);
+        envelope.set_sustain_level(0.5);
+        envelope.set_release_time(10.0);
+        envelope.set_release_tension(0.0);
+        envelope.start();
+
+        render(&mut envelope, 25);
+        assert_eq!(envelope.stage, EnvelopeStage::Sustain);
+        envelope.legato();
+        assert_eq!(envelope.stage, EnvelopeStage::Sustain);
+        assert_eq!(render(&mut envelope, 1)[0], 0.5);
+
+        // Once released it starts over, from where the release got to
+        envelope.release();
+        assert_near(*render(&mut envelope, 5).last().unwrap(), 0.25);
+        envelope.legato();
+        assert_eq!(envelope.stage, EnvelopeStage::Attack);
+        assert_near(render(&mut envelope, 1)[0], 0.325);
+
+        render(&mut envelope, 100);
+        envelope.release();
+        render(&mut envelope, 10);
+        assert_eq!(envelope.stage, EnvelopeStage::Idle);
+        envelope.legato();
+        assert_eq!(envelope.stage, EnvelopeStage::Attack);
+    }
+
+    #[test]
+    fn stages_without_time_are_skipped() {
+        let mut envelope = envelope();
+        envelope.set_sustain_level(0.5);
+        envelope.start();
+        assert_eq!(envelope.stage, EnvelopeStage::Sustain);
+        assert_eq!(render(&mut envelope, 1)[0], 0.5);
+
+        envelope.release();
+        assert_eq!(envelope.stage, EnvelopeStage::Idle);
+
+        envelope.set_sustain_level(0.0);
+        envelope.start();
+        assert_eq!(envelope.stage, EnvelopeStage::Idle);
+        assert_eq!(render(&mut envelope, 3), [0.0; 3]);
+    }
+}
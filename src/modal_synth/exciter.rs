@@ -1,133 +1,156 @@
-use rand::Rng;
-use rand_pcg::Pcg32;
 use std::sync::Arc;
 
 use crate::{
-    constants::DEFAULT_SAMPLE_RATE,
-    modal_synth::envelope::Envelope,
-    params::{ParamBuffers, PockyplockyParams},
+    constants::{DEFAULT_SAMPLE_RATE, MAX_BLOCK_SIZE},
+    modal_synth::{
+        bow::Bow,
+        breath_noise::BreathNoise,
+        contact::HertzMallet,
+        exciter_source::{ExciterLayer, Strike, sensitive_velocity},
+        feedback::Feedback,
+        hann_mallet::HannMallet,
+        impulse_strike::ImpulseStrike,
+        jet::Jet,
+        particles::ParticleShaker,
+        sample_player::SampleStrike,
+        strike_pattern::StrikeSequence,
+    },
+    params::{MalletModel, ParamBuffers, PockyplockyParams},
 };
 
-pub struct HannBurst {
-    len: usize,
-    pos: usize,
-    scale: f32,
-    inv_len: f32,
-}
-
-impl HannBurst {
-    pub fn new() -> Self {
-        Self {
-            len: 0,
-            pos: 0,
-            scale: 0.0,
-            inv_len: 0.0,
-        }
-    }
-
-    pub fn start(
-        &mut self,
-        sample_rate: f32,
-        fundamental: f32,
-        gain: f32,
-        hardness: f32,
-        velocity: f32,
-    ) {
-        let period_s = 1.0 / fundamental;
-        let hardness_factor = hardness * velocity;
-        let cycles = 0.4 - (hardness_factor * 0.36);
-        self.len = ((period_s * cycles * sample_rate).round() as usize).max(3);
-        self.scale = gain / (self.len as f32).sqrt();
-        self.pos = 0;
-        self.inv_len = 1.0 / (self.len as f32 - 1.0);
-    }
-
-    pub fn process(&mut self) -> f32 {
-        if self.pos < self.len {
-            let normalized_pos = self.pos as f32 * self.inv_len;
-            let v = 0.5 * (1.0 - (2.0 * std::f32::consts::PI * normalized_pos).cos()) * self.scale;
-            self.pos += 1;
-            v
-        } else {
-            0.0
-        }
-    }
-
-    pub fn reset(&mut self) {
-        self.scale = 0.0;
-        self.inv_len = 0.0;
-        self.len = 0;
-        self.pos = 0;
-    }
-}
-
+/// Everything that excites the resonator. The layers are mixed into the input of the resonator,
+/// the physical mallet, the bow, the jet and the feedback react to the movement of the bar, so
+/// they're run sample by sample along with it.
 pub struct Exciter {
     params: Arc<PockyplockyParams>,
     sample_rate: f32,
-    breath_envelope: Envelope,
-    trigger: f32,
-    hann: HannBurst,
-    prng: Pcg32,
-    render_noise: bool,
+    layers: Vec<ExciterLayer>,
+    hertz: HertzMallet,
+    bow: Bow,
+    jet: Jet,
+    feedback: Feedback,
+    strikes: StrikeSequence,
+    contact_strikes: StrikeSequence,
     velocity_sqrt: f32,
+    expression: f32, // Set by pressure or the mod wheel, scales the bow velocity and jet pressure
+    feedback_decay: f32,
 }
 
 impl Exciter {
-    pub fn new(params: Arc<PockyplockyParams>) -> Self {
+    /// Every exciter should get its own `seed`, so they don't all play the same noise.
+    pub fn new(params: Arc<PockyplockyParams>, seed: u64) -> Self {
+        let layers = vec![
+            ExciterLayer::new(Box::new(ImpulseStrike::new(params.clone()))),
+            ExciterLayer::new(Box::new(HannMallet::new(params.clone()))),
+            ExciterLayer::new(Box::new(BreathNoise::new(params.clone(), seed))),
+            ExciterLayer::new(Box::new(SampleStrike::new(params.clone()))),
+            ExciterLayer::new(Box::new(ParticleShaker::new(params.clone(), seed))),
+        ];
+
         Self {
             params,
             sample_rate: DEFAULT_SAMPLE_RATE,
-            breath_envelope: Envelope::new(),
-            trigger: 0.0,
-            hann: HannBurst::new(),
-            prng: Pcg32::new(12345, 67890),
-            render_noise: false,
+            layers,
+            hertz: HertzMallet::new(),
+            bow: Bow::new(),
+            jet: Jet::new(seed),
+            feedback: Feedback::new(),
+            strikes: StrikeSequence::new(),
+            contact_strikes: StrikeSequence::new(),
             velocity_sqrt: 0.0,
+            expression: 1.0,
+            feedback_decay: 1.0,
         }
     }
 
     pub fn set_sample_rate(&mut self, sample_rate: f32) {
         self.sample_rate = sample_rate;
-        self.breath_envelope.set_sample_rate(sample_rate);
+        for layer in &mut self.layers {
+            layer.set_sample_rate(sample_rate);
+        }
+        self.hertz.set_sample_rate(sample_rate);
+        self.bow.set_sample_rate(sample_rate);
+        self.jet.set_sample_rate(sample_rate);
+        self.feedback.set_sample_rate(sample_rate);
     }
 
     pub fn reset(&mut self) {
-        self.breath_envelope.reset();
-        self.trigger = 0.0;
-        self.hann.reset();
-        self.render_noise = false;
+        for layer in &mut self.layers {
+            layer.reset();
+        }
+        self.hertz.reset();
+        self.bow.reset();
+        self.jet.reset();
+        self.feedback.reset();
+        self.strikes.reset();
+        self.contact_strikes.reset();
         self.velocity_sqrt = 0.0;
     }
 
-    pub fn start(&mut self, fundamental: f32, velocity: f32) {
-        self.breath_envelope
-            .set_attack_time(self.params.breath_attack.value());
-        self.breath_envelope
-            .set_attack_curve(self.params.breath_attack_shape.value());
-        self.breath_envelope
-            .set_decay_time(self.params.breath_decay.value());
-        self.breath_envelope
-            .set_decay_curve(self.params.breath_decay_shape.value());
-        self.breath_envelope.start();
-
+    /// Starts the note, returns how long the exciter keeps going in seconds: until the last strike of
+    /// the strike pattern or until the layers have died out.
+    pub fn start(&mut self, fundamental: f32, velocity: f32) -> f32 {
         self.velocity_sqrt = velocity.sqrt();
-        self.trigger = if self.params.strike.value() {
-            self.velocity_sqrt
-        } else {
-            0.0
-        };
-
-        if self.params.mallet.value() {
-            self.hann.start(
-                self.sample_rate,
-                fundamental,
-                self.velocity_sqrt,
-                self.params.mallet_hardness.value(),
-                velocity,
-            );
+        self.strikes.start(
+            self.params.strike_pattern.value(),
+            velocity,
+            self.params.strike_spacing.value() * 0.001 * self.sample_rate,
+            self.params.strike_spacing_decay.value(),
+            self.params.strike_velocity_falloff.value(),
+        );
+        self.contact_strikes = self.strikes.clone();
+
+        let mut duration = self.strikes.duration() as f32 / self.sample_rate;
+        for layer in &mut self.layers {
+            duration = duration.max(layer.start(fundamental, velocity));
+        }
+        duration
+    }
+
+    /// Shakes the layers again, with `strength` from 0.0 to 1.0.
+    pub fn shake(&mut self, strength: f32) {
+        for layer in &mut self.layers {
+            layer.shake(strength);
         }
+    }
 
-        self.render_noise = self.params.breath_level.value() > 0.0;
+    /// Starts drawing the bow, it keeps going until `release()`. Strikes of a roll don't restart it.
+    pub fn start_bow(&mut self) {
+        self.bow.start();
+    }
+
+    /// Starts blowing the jet, it keeps going until `release()`. `decay` is the decay time of the
+    /// fundamental in seconds.
+    pub fn start_jet(&mut self, fundamental: f32, decay: f32) {
+        self.jet.start(fundamental, decay);
+    }
+
+    /// Starts feeding the resonator back into itself, until `release()`. `decay` is the decay time
+    /// of the fundamental in seconds.
+    pub fn start_feedback(&mut self, decay: f32) {
+        self.feedback_decay = decay;
+        self.feedback.start();
+    }
+
+    /// Stops the bow, the jet, the feedback and the layers that keep going while the note is held.
+    pub fn release(&mut self) {
+        self.bow.release();
+        self.jet.release();
+        self.feedback.release();
+        for layer in &mut self.layers {
+            layer.release();
+        }
+    }
+
+    pub fn set_expression(&mut self, expression: f32) {
+        self.expression = expression;
+    }
+
+    /// Sets the level of a sustained breath, from 0.0 to 1.0.
+    pub fn set_breath(&mut self, breath: f32) {
+        for layer in &mut self.layers {
+            layer.set_breath(breath);
+        }
     }
 
     pub fn process_block(
@@ -136,30 +159,73 @@ impl Exciter {
         block_len: usize,
         param_buffers: &ParamBuffers,
     ) {
-        let noise_level_buffer = param_buffers.get_noise_level_buffer();
-        let envelope_values = self.breath_envelope.process_block(block_len);
-
-        if self.render_noise {
-            for i in 0..block_len {
-                let noise_sample = self.prng.gen_range(-1.0..=1.0)
-                    * envelope_values[i]
-                    * noise_level_buffer[i]
-                    * self.velocity_sqrt;
-
-                output[i] = noise_sample + self.trigger;
-                self.trigger = 0.0;
-            }
-        } else {
-            for sample in output.iter_mut().take(block_len) {
-                *sample = self.trigger;
-                self.trigger = 0.0;
+        self.bow.set_params(
+            self.params.bow_pressure.value(),
+            self.params.bow_velocity.value() * self.velocity_sqrt * self.expression,
+        );
+        // Velocity is left out, soft notes would be blown too weakly to speak
+        self.jet.set_params(
+            self.params.jet_pressure.value() * self.expression,
+            self.params.jet_ratio.value(),
+            self.params.jet_noise.value(),
+        );
+        self.feedback.set_params(
+            self.params.feedback_amount.value(),
+            self.feedback_decay,
+            self.params.feedback_delay.value(),
+            self.params.feedback_invert.value(),
+        );
+
+        // Every strike of the strike pattern hits the same resonator
+        let mut strikes = [Strike::default(); MAX_BLOCK_SIZE];
+        let mut num_strikes = 0;
+        for position in 0..block_len {
+            if let Some(velocity) = self.strikes.next_sample() {
+                strikes[num_strikes] = Strike { position, velocity };
+                num_strikes += 1;
             }
         }
 
-        if self.params.mallet.value() {
-            for sample in output.iter_mut().take(block_len) {
-                *sample += self.hann.process();
-            }
+        let output = &mut output[..block_len];
+        output.fill(0.0);
+        for layer in &mut self.layers {
+            layer.process_block(&strikes[..num_strikes], param_buffers, output);
         }
     }
+
+    /// Returns the force of the physical mallet for the next sample, given how far the bar has
+    /// moved under it. This is zero when the classic mallet model is used.
+    pub fn process_contact(&mut self, bar_displacement: f32) -> f32 {
+        // Follows the strike pattern on its own, since it runs in a different loop
+        if let Some(velocity) = self.contact_strikes.next_sample()
+            && self.params.mallet.value()
+            && self.params.mallet_model.value() == MalletModel::Physical
+        {
+            let velocity =
+                sensitive_velocity(velocity, self.params.mallet_velocity_sensitivity.value());
+            self.hertz.start(
+                velocity.sqrt() * self.params.mallet_level.value(),
+                self.params.mallet_material.value(),
+                self.params.mallet_hardness.value(),
+            );
+        }
+
+        self.hertz.process(bar_displacement)
+    }
+
+    /// Returns the friction force of the bow for the next sample, given how fast the bar moves at
+    /// the bow.
+    pub fn process_bow(&mut self, bar_velocity: f32) -> f32 {
+        self.bow.process(bar_velocity)
+    }
+
+    /// Returns the force of the jet for the next sample, given the output of the resonator.
+    pub fn process_jet(&mut self, resonator_output: f32) -> f32 {
+        self.jet.process(resonator_output)
+    }
+
+    /// Returns the feedback for the next sample, given the output of the resonator.
+    pub fn process_feedback(&mut self, resonator_output: f32) -> f32 {
+        self.feedback.process(resonator_output)
+    }
 }
@@ -0,0 +1,177 @@
+use crate::{
+    constants::MAX_BLOCK_SIZE,
+    modal_synth::envelope::Envelope,
+    params::{BreathAttackCurve, BreathDecayCurve, ParamBuffers},
+};
+
+/// A strike of the strike pattern, at `position` samples into the block.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Strike {
+    pub position: usize,
+    pub velocity: f32,
+}
+
+/// The envelope a layer is shaped with, started with every note.
+pub struct EnvelopeShape {
+    pub attack_ms: f32,
+    pub attack_curve: BreathAttackCurve,
+    pub decay_ms: f32,
+    pub decay_curve: BreathDecayCurve,
+}
+
+/// A sound that excites the resonator, like a mallet or a breath. Sources read their own params,
+/// the layer they're in takes care of the level, velocity sensitivity and envelope.
+pub trait ExciterSource: Send {
+    /// Whether the source plays, read at the start of every note.
+    fn enabled(&self) -> bool;
+
+    fn level(&self) -> f32 {
+        1.0
+    }
+
+    /// How much the velocity matters, from 0.0 where every note plays at full velocity to 1.0.
+    fn velocity_sensitivity(&self) -> f32 {
+        1.0
+    }
+
+    /// The envelope the source is shaped with, `None` leaves it as it is.
+    fn envelope(&self) -> Option<EnvelopeShape> {
+        None
+    }
+
+    fn set_sample_rate(&mut self, _sample_rate: f32) {}
+
+    fn reset(&mut self);
+
+    /// Starts a note, returns how long the source keeps going in seconds. Strikes of the strike
+    /// pattern are passed to `process_block()`.
+    fn start(&mut self, _fundamental: f32, _velocity: f32) -> f32 {
+        0.0
+    }
+
+    fn release(&mut self) {}
+
+    /// Shakes the source while the note is ringing, with `strength` from 0.0 to 1.0.
+    fn shake(&mut self, _strength: f32) {}
+
+    /// Sets the level of a sustained breath, from 0.0 to 1.0.
+    fn set_breath(&mut self, _breath: f32) {}
+
+    /// Renders a block into `output`, which starts out silent.
+    fn process_block(
+        &mut self,
+        strikes: &[Strike],
+        param_buffers: &ParamBuffers,
+        output: &mut [f32],
+    );
+}
+
+/// Scales the velocity towards full velocity, as the velocity sensitivity goes down.
+pub fn sensitive_velocity(velocity: f32, sensitivity: f32) -> f32 {
+    1.0 - sensitivity * (1.0 - velocity)
+}
+
+/// One source of the exciter, mixed in with the others.
+pub struct ExciterLayer {
+    source: Box<dyn ExciterSource>,
+    envelope: Envelope,
+    has_envelope: bool,
+    active: bool,
+}
+
+impl ExciterLayer {
+    pub fn new(source: Box<dyn ExciterSource>) -> Self {
+        Self {
+            source,
+            envelope: Envelope::new(),
+            has_envelope: false,
+            active: false,
+        }
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.source.set_sample_rate(sample_rate);
+        self.envelope.set_sample_rate(sample_rate);
+    }
+
+    pub fn reset(&mut self) {
+        self.source.reset();
+        self.envelope.reset();
+        self.active = false;
+    }
+
+    pub fn start(&mut self, fundamental: f32, velocity: f32) -> f32 {
+        self.active = self.source.enabled();
+        if !self.active {
+            return 0.0;
+        }
+
+        let shape = self.source.envelope();
+        self.has_envelope = shape.is_some();
+        if let Some(shape) = shape {
+            self.envelope.set_attack_time(shape.attack_ms);
+            self.envelope.set_attack_curve(shape.attack_curve);
+            self.envelope.set_decay_time(shape.decay_ms);
+            self.envelope.set_decay_curve(shape.decay_curve);
+            self.envelope.start();
+        }
+
+        let velocity = sensitive_velocity(velocity, self.source.velocity_sensitivity());
+        self.source.start(fundamental, velocity)
+    }
+
+    pub fn release(&mut self) {
+        self.source.release();
+    }
+
+    pub fn shake(&mut self, strength: f32) {
+        if self.active {
+            self.source.shake(strength);
+        }
+    }
+
+    pub fn set_breath(&mut self, breath: f32) {
+        self.source.set_breath(breath);
+    }
+
+    /// Adds the layer to `output`.
+    pub fn process_block(
+        &mut self,
+        strikes: &[Strike],
+        param_buffers: &ParamBuffers,
+        output: &mut [f32],
+    ) {
+        if !self.active {
+            return;
+        }
+
+        let block_len = output.len();
+        let sensitivity = self.source.velocity_sensitivity();
+        let mut layer_strikes = [Strike::default(); MAX_BLOCK_SIZE];
+        for (layer_strike, strike) in layer_strikes.iter_mut().zip(strikes) {
+            *layer_strike = Strike {
+                position: strike.position,
+                velocity: sensitive_velocity(strike.velocity, sensitivity),
+            };
+        }
+
+        let mut buffer = [0.0; MAX_BLOCK_SIZE];
+        self.source.process_block(
+            &layer_strikes[..strikes.len()],
+            param_buffers,
+            &mut buffer[..block_len],
+        );
+
+        let level = self.source.level();
+        if self.has_envelope {
+            let envelope_values = self.envelope.process_block(block_len);
+            for i in 0..block_len {
+                output[i] += buffer[i] * envelope_values[i] * level;
+            }
+        } else {
+            for i in 0..block_len {
+                output[i] += buffer[i] * level;
+            }
+        }
+    }
+}
@@ -0,0 +1,116 @@
+use crate::{
+    constants::DEFAULT_SAMPLE_RATE,
+    modal_synth::{delay_line::DelayLine, resonator::T60_DECAY_FACTOR},
+};
+
+// Loop gain at the fundamental at full Feedback Amount. Above a loop gain of one the modes keep
+// growing until the saturator holds them back, below it they just ring longer.
+const MAX_LOOP_GAIN: f32 = 3.0;
+
+// Peak gain of a mode times the amount it decays per sample
+const RESONATOR_GAIN: f32 = 1.0 / (4.0 * std::f32::consts::PI);
+
+// Level at which the saturator starts to hold the feedback back
+const SATURATION_LEVEL: f32 = 0.3;
+
+// The limiter turns the feedback down when the resonator gets louder than this
+const LIMITER_CEILING: f32 = 0.7;
+const LIMITER_RELEASE_TIME: f32 = 0.1;
+
+// Longest feedback delay, in seconds
+const MAX_FEEDBACK_DELAY: f32 = 0.05;
+
+// Time it takes for the feedback to fade out after release, in seconds
+const FEEDBACK_RELEASE_TIME: f32 = 0.1;
+
+/// Feeds the output of the resonator back into its input through a saturator and a delay. With
+/// enough feedback the modes sustain and slowly bloom into a drone.
+pub struct Feedback {
+    sample_rate: f32,
+    gain: f32,
+    delay_samples: f32,
+    delay: DelayLine,
+    envelope: f32,
+    limiter_release: f32,
+    level: f32, // Fades the feedback out after release
+    release_coefficient: f32,
+    active: bool,
+}
+
+impl Feedback {
+    pub fn new() -> Self {
+        let mut feedback = Self {
+            sample_rate: DEFAULT_SAMPLE_RATE,
+            gain: 0.0,
+            delay_samples: 0.0,
+            delay: DelayLine::new(),
+            envelope: 0.0,
+            limiter_release: 0.0,
+            level: 0.0,
+            release_coefficient: 0.0,
+            active: false,
+        };
+        feedback.set_sample_rate(DEFAULT_SAMPLE_RATE);
+        feedback
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+        self.delay
+            .set_max_delay((MAX_FEEDBACK_DELAY * sample_rate).ceil() as usize);
+        self.limiter_release = (T60_DECAY_FACTOR / (LIMITER_RELEASE_TIME * sample_rate)).exp();
+        self.release_coefficient = (T60_DECAY_FACTOR / (FEEDBACK_RELEASE_TIME * sample_rate)).exp();
+    }
+
+    pub fn reset(&mut self) {
+        self.delay.reset();
+        self.envelope = 0.0;
+        self.level = 0.0;
+        self.active = false;
+    }
+
+    pub fn start(&mut self) {
+        self.level = 1.0;
+        self.active = true;
+    }
+
+    /// Fades the feedback out, so the note can ring out.
+    pub fn release(&mut self) {
+        self.active = false;
+    }
+
+    /// `amount` goes from 0.0 to 1.0, `decay` is the decay time of the fundamental in seconds and
+    /// `delay_ms` is the extra delay in the loop. An inverted loop feeds back with the opposite
+    /// polarity, which favours different modes.
+    pub fn set_params(&mut self, amount: f32, decay: f32, delay_ms: f32, invert: bool) {
+        // The loop gain has to make up for the decay of the modes, which is less per sample for
+        // longer decays and higher sample rates
+        let loss = -T60_DECAY_FACTOR / (decay * self.sample_rate);
+        let gain = amount * MAX_LOOP_GAIN * loss / RESONATOR_GAIN;
+        self.gain = if invert { -gain } else { gain };
+        self.delay_samples = delay_ms * 0.001 * self.sample_rate;
+    }
+
+    /// Returns the input for the resonator, given its last output.
+    pub fn process(&mut self, resonator_output: f32) -> f32 {
+        if !self.active {
+            self.level *= self.release_coefficient;
+            if self.level < 1e-5 {
+                return 0.0;
+            }
+        }
+
+        self.envelope = resonator_output
+            .abs()
+            .max(self.envelope * self.limiter_release);
+        let limiter = if self.envelope > LIMITER_CEILING {
+            LIMITER_CEILING / self.envelope
+        } else {
+            1.0
+        };
+
+        let saturated = (resonator_output / SATURATION_LEVEL).tanh() * SATURATION_LEVEL;
+        self.delay.write(saturated);
+        self.delay.read(self.delay_samples) * self.gain * limiter * self.level
+    }
+}
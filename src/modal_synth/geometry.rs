@@ -0,0 +1,186 @@
+use std::{f64::consts::PI, sync::LazyLock};
+
+use crate::{modal_synth::modes::NUM_MODES, params::Geometry};
+
+// Number of points the Bessel integrals are evaluated at. The integrands are periodic, so the
+// trapezoidal rule converges very quickly as long as there are more points than the argument.
+const BESSEL_POINTS: usize = 64;
+
+// Roots are searched for in steps this large, small enough to never skip over a pair of roots
+const ROOT_SEARCH_STEP: f64 = 0.1;
+
+// Highest wave number the roots of bars are searched up to, beyond their 24th mode
+const MAX_BAR_ROOT: f64 = 80.0;
+
+// Highest argument the roots of membranes and plates are searched up to. Their modes are much
+// closer together than those of bars, the 24th mode is well below it.
+const MAX_CIRCULAR_ROOT: f64 = 30.0;
+
+// Highest circumferential order of the modes of membranes and plates
+const MAX_ORDER: usize = 16;
+
+// Highest number of half waves along either side of a rectangular plate
+const MAX_HALF_WAVES: usize = 12;
+
+/// Bessel function of the first kind, from its integral representation.
+fn bessel_j(order: usize, x: f64) -> f64 {
+    let step = PI / BESSEL_POINTS as f64;
+    let mut sum = 0.0;
+    for i in 0..=BESSEL_POINTS {
+        let t = i as f64 * step;
+        let weight = if i == 0 || i == BESSEL_POINTS {
+            0.5
+        } else {
+            1.0
+        };
+        sum += weight * (order as f64 * t - x * t.sin()).cos();
+    }
+    sum * step / PI
+}
+
+/// Modified Bessel function of the first kind, from its integral representation.
+fn bessel_i(order: usize, x: f64) -> f64 {
+    let step = PI / BESSEL_POINTS as f64;
+    let mut sum = 0.0;
+    for i in 0..=BESSEL_POINTS {
+        let t = i as f64 * step;
+        let weight = if i == 0 || i == BESSEL_POINTS {
+            0.5
+        } else {
+            1.0
+        };
+        sum += weight * (x * t.cos()).exp() * (order as f64 * t).cos();
+    }
+    sum * step / PI
+}
+
+/// Finds the roots of `f` between `start` and `end`, at most `max_roots` of them.
+fn find_roots(f: impl Fn(f64) -> f64, start: f64, end: f64, max_roots: usize) -> Vec<f64> {
+    let mut roots = Vec::new();
+    let mut low = start;
+    let mut f_low = f(low);
+    while low < end && roots.len() < max_roots {
+        let high = low + ROOT_SEARCH_STEP;
+        let f_high = f(high);
+        if f_low.signum() != f_high.signum() {
+            let (mut a, mut b) = (low, high);
+            for _ in 0..30 {
+                let middle = 0.5 * (a + b);
+                if f(middle).signum() == f_low.signum() {
+                    a = middle;
+                } else {
+                    b = middle;
+                }
+            }
+            roots.push(0.5 * (a + b));
+        }
+        low = high;
+        f_low = f_high;
+    }
+    roots
+}
+
+/// Frequency ratios of a bar with free ends, like a xylophone or glockenspiel bar. The roots of
+/// cos β·cosh β = 1 are the wave numbers of the modes, frequencies go with their square.
+fn free_bar() -> Vec<f64> {
+    find_roots(|b| b.cos() - 1.0 / b.cosh(), 1.0, MAX_BAR_ROOT, NUM_MODES)
+        .iter()
+        .map(|b| b * b)
+        .collect()
+}
+
+/// Frequency ratios of a bar clamped at one end, like a kalimba tine or a tuning fork prong. The
+/// wave numbers are the roots of cos β·cosh β = -1.
+fn clamped_bar() -> Vec<f64> {
+    find_roots(|b| b.cos() + 1.0 / b.cosh(), 1.0, MAX_BAR_ROOT, NUM_MODES)
+        .iter()
+        .map(|b| b * b)
+        .collect()
+}
+
+/// Frequency ratios of an ideal circular membrane, like a drum head. Frequencies go with the zeros
+/// of the Bessel functions. The first zero of J_m lies above m, below that it's too close to zero
+/// to search for its sign.
+fn membrane() -> Vec<f64> {
+    let mut frequencies = Vec::new();
+    for order in 0..MAX_ORDER {
+        let start = order as f64 + 0.5;
+        frequencies.extend(find_roots(
+            |x| bessel_j(order, x),
+            start,
+            MAX_CIRCULAR_ROOT,
+            NUM_MODES,
+        ));
+    }
+    frequencies
+}
+
+/// Frequency ratios of a circular plate clamped at its rim, like a cymbal held too tightly or a
+/// thick drum head. Frequencies go with the square of the roots of
+/// J_m(λ)·I_m+1(λ) + I_m(λ)·J_m+1(λ) = 0.
+fn clamped_plate() -> Vec<f64> {
+    let mut frequencies = Vec::new();
+    for order in 0..MAX_ORDER {
+        let roots = find_roots(
+            |x| {
+                // Divided by I_m, which grows exponentially, the ratio stays finite
+                bessel_j(order, x) * bessel_i(order + 1, x) / bessel_i(order, x)
+                    + bessel_j(order + 1, x)
+            },
+            order as f64 + 0.5,
+            MAX_CIRCULAR_ROOT,
+            NUM_MODES,
+        );
+        frequencies.extend(roots.iter().map(|x| x * x));
+    }
+    frequencies
+}
+
+/// Frequency ratios of a rectangular plate supported at its edges, `aspect` is how many times
+/// longer it is than wide. The modes have m half waves along its length and n across it.
+fn rectangular_plate(aspect: f64) -> [f64; MAX_HALF_WAVES * MAX_HALF_WAVES] {
+    std::array::from_fn(|i| {
+        let m = (i / MAX_HALF_WAVES + 1) as f64;
+        let n = (i % MAX_HALF_WAVES + 1) as f64;
+        (m / aspect).powi(2) + n * n
+    })
+}
+
+/// Sorts the frequencies and scales the lowest ones relative to the lowest one. When there are
+/// fewer than `NUM_MODES` the rest is left at 0.0.
+fn ratios(frequencies: &mut [f64]) -> [f32; NUM_MODES] {
+    frequencies.sort_by(f64::total_cmp);
+    let lowest = frequencies.first().copied().unwrap_or(1.0);
+    std::array::from_fn(|i| {
+        frequencies
+            .get(i)
+            .map_or(0.0, |frequency| (frequency / lowest) as f32)
+    })
+}
+
+// Finding the roots of the Bessel functions takes a while, so the geometries that don't have
+// params are only solved once
+static FREE_BAR: LazyLock<[f32; NUM_MODES]> = LazyLock::new(|| ratios(&mut free_bar()));
+static CLAMPED_BAR: LazyLock<[f32; NUM_MODES]> = LazyLock::new(|| ratios(&mut clamped_bar()));
+static MEMBRANE: LazyLock<[f32; NUM_MODES]> = LazyLock::new(|| ratios(&mut membrane()));
+static CLAMPED_PLATE: LazyLock<[f32; NUM_MODES]> = LazyLock::new(|| ratios(&mut clamped_plate()));
+
+/// Solves the geometries up front, so that doesn't happen on the audio thread.
+pub fn prepare_geometries() {
+    LazyLock::force(&FREE_BAR);
+    LazyLock::force(&CLAMPED_BAR);
+    LazyLock::force(&MEMBRANE);
+    LazyLock::force(&CLAMPED_PLATE);
+}
+
+/// The frequency ratios of the lowest modes of a geometry, relative to the lowest one. `aspect`
+/// only applies to the rectangular plate.
+pub fn geometry_ratios(geometry: Geometry, aspect: f32) -> [f32; NUM_MODES] {
+    match geometry {
+        Geometry::FreeBar => *FREE_BAR,
+        Geometry::ClampedBar => *CLAMPED_BAR,
+        Geometry::Membrane => *MEMBRANE,
+        Geometry::ClampedPlate => *CLAMPED_PLATE,
+        Geometry::RectangularPlate => ratios(&mut rectangular_plate(aspect as f64)),
+    }
+}
@@ -0,0 +1,139 @@
+use std::sync::Arc;
+
+use crate::{
+    constants::DEFAULT_SAMPLE_RATE,
+    modal_synth::exciter_source::{EnvelopeShape, ExciterSource, Strike},
+    params::{MalletModel, ParamBuffers, PockyplockyParams},
+};
+
+pub struct HannBurst {
+    len: usize,
+    pos: usize,
+    scale: f32,
+    inv_len: f32,
+}
+
+impl HannBurst {
+    pub fn new() -> Self {
+        Self {
+            len: 0,
+            pos: 0,
+            scale: 0.0,
+            inv_len: 0.0,
+        }
+    }
+
+    pub fn start(
+        &mut self,
+        sample_rate: f32,
+        fundamental: f32,
+        gain: f32,
+        hardness: f32,
+        velocity: f32,
+    ) {
+        let period_s = 1.0 / fundamental;
+        let hardness_factor = hardness * velocity;
+        let cycles = 0.4 - (hardness_factor * 0.36);
+        self.len = ((period_s * cycles * sample_rate).round() as usize).max(3);
+        self.scale = gain / (self.len as f32).sqrt();
+        self.pos = 0;
+        self.inv_len = 1.0 / (self.len as f32 - 1.0);
+    }
+
+    pub fn process(&mut self) -> f32 {
+        if self.pos < self.len {
+            let normalized_pos = self.pos as f32 * self.inv_len;
+            let v = 0.5 * (1.0 - (2.0 * std::f32::consts::PI * normalized_pos).cos()) * self.scale;
+            self.pos += 1;
+            v
+        } else {
+            0.0
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.scale = 0.0;
+        self.inv_len = 0.0;
+        self.len = 0;
+        self.pos = 0;
+    }
+}
+
+/// The classic mallet, a Hann shaped pulse for every strike that gets shorter as the mallet gets
+/// harder. The physical mallet is part of the exciter instead, since it reacts to the bar.
+pub struct HannMallet {
+    params: Arc<PockyplockyParams>,
+    sample_rate: f32,
+    fundamental: f32,
+    hann: HannBurst,
+}
+
+impl HannMallet {
+    pub fn new(params: Arc<PockyplockyParams>) -> Self {
+        Self {
+            params,
+            sample_rate: DEFAULT_SAMPLE_RATE,
+            fundamental: 0.0,
+            hann: HannBurst::new(),
+        }
+    }
+}
+
+impl ExciterSource for HannMallet {
+    fn enabled(&self) -> bool {
+        self.params.mallet.value() && self.params.mallet_model.value() == MalletModel::Classic
+    }
+
+    fn level(&self) -> f32 {
+        self.params.mallet_level.value()
+    }
+
+    fn velocity_sensitivity(&self) -> f32 {
+        self.params.mallet_velocity_sensitivity.value()
+    }
+
+    fn envelope(&self) -> Option<EnvelopeShape> {
+        let params = &self.params;
+        params.mallet_envelope.value().then(|| EnvelopeShape {
+            attack_ms: params.mallet_envelope_attack.value(),
+            attack_curve: params.mallet_envelope_attack_shape.value(),
+            decay_ms: params.mallet_envelope_decay.value(),
+            decay_curve: params.mallet_envelope_decay_shape.value(),
+        })
+    }
+
+    fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+    }
+
+    fn reset(&mut self) {
+        self.hann.reset();
+    }
+
+    fn start(&mut self, fundamental: f32, _velocity: f32) -> f32 {
+        self.fundamental = fundamental;
+        0.0
+    }
+
+    fn process_block(
+        &mut self,
+        strikes: &[Strike],
+        _param_buffers: &ParamBuffers,
+        output: &mut [f32],
+    ) {
+        let hardness = self.params.mallet_hardness.value();
+        let mut strikes = strikes.iter().peekable();
+        for (i, sample) in output.iter_mut().enumerate() {
+            if let Some(strike) = strikes.next_if(|strike| strike.position == i) {
+                self.hann.start(
+                    self.sample_rate,
+                    self.fundamental,
+                    strike.velocity.sqrt(),
+                    hardness,
+                    strike.velocity,
+                );
+            }
+            *sample = self.hann.process();
+        }
+    }
+}
@@ -0,0 +1,54 @@
+use std::sync::Arc;
+
+use crate::{
+    modal_synth::exciter_source::{EnvelopeShape, ExciterSource, Strike},
+    params::{ParamBuffers, PockyplockyParams},
+};
+
+/// A single sample impulse for every strike, the sharpest possible attack.
+pub struct ImpulseStrike {
+    params: Arc<PockyplockyParams>,
+}
+
+impl ImpulseStrike {
+    pub fn new(params: Arc<PockyplockyParams>) -> Self {
+        Self { params }
+    }
+}
+
+impl ExciterSource for ImpulseStrike {
+    fn enabled(&self) -> bool {
+        self.params.strike.value()
+    }
+
+    fn level(&self) -> f32 {
+        self.params.strike_level.value()
+    }
+
+    fn velocity_sensitivity(&self) -> f32 {
+        self.params.strike_velocity_sensitivity.value()
+    }
+
+    fn envelope(&self) -> Option<EnvelopeShape> {
+        let params = &self.params;
+        params.strike_envelope.value().then(|| EnvelopeShape {
+            attack_ms: params.strike_envelope_attack.value(),
+            attack_curve: params.strike_envelope_attack_shape.value(),
+            decay_ms: params.strike_envelope_decay.value(),
+            decay_curve: params.strike_envelope_decay_shape.value(),
+        })
+    }
+
+    fn reset(&mut self) {}
+
+    fn process_block(
+        &mut self,
+        strikes: &[Strike],
+        _param_buffers: &ParamBuffers,
+        output: &mut [f32],
+    ) {
+        for strike in strikes {
+            output[strike.position] += strike.velocity.sqrt();
+        }
+    }
+}
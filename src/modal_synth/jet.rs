@@ -0,0 +1,133 @@
+use rand::Rng;
+use rand_pcg::Pcg32;
+
+use crate::{constants::DEFAULT_SAMPLE_RATE, modal_synth::delay_line::DelayLine};
+
+// Breath pressure at full Jet Pressure. The bar starts speaking at around two thirds of this.
+const MAX_PRESSURE: f32 = 1.2;
+
+// Strength of the jet at the default sample rate, for a decay of one second. Together with the
+// feedback gain this sets how loud the blown bar gets, while keeping the loop gain the same.
+const JET_GAIN: f32 = 0.003;
+const FEEDBACK_GAIN: f32 = 3.3;
+
+// Longest jet delay, in seconds
+const MAX_JET_DELAY: f32 = 0.05;
+
+// Time it takes to start and stop blowing, in seconds
+const JET_ATTACK_TIME: f32 = 0.03;
+const JET_RELEASE_TIME: f32 = 0.08;
+
+// Cutoff of the DC blocker after the jet, in Hz
+const DC_BLOCKER_FREQUENCY: f32 = 20.0;
+
+/// An air jet blown across the edge of an opening, like blowing over a bottle. The jet is deflected
+/// in and out of the opening by the movement of the resonator, with a delay for the time it takes
+/// to travel across. This feeds back into the resonator and makes the fundamental self-oscillate
+/// once the pressure is high enough. The jet follows the flute in the Synthesis ToolKit, with the
+/// resonator taking the place of the bore.
+pub struct Jet {
+    sample_rate: f32,
+    gain: f32,
+    pressure: f32,
+    target_pressure: f32,
+    jet_ratio: f32,
+    noise: f32,
+    period: f32, // Period of the fundamental, in samples
+    delay: DelayLine,
+    dc_x: f32,
+    dc_y: f32,
+    dc_coefficient: f32,
+    attack_coefficient: f32,
+    release_coefficient: f32,
+    blowing: bool,
+    prng: Pcg32,
+}
+
+impl Jet {
+    pub fn new(seed: u64) -> Self {
+        let mut jet = Self {
+            sample_rate: DEFAULT_SAMPLE_RATE,
+            gain: 0.0,
+            pressure: 0.0,
+            target_pressure: 0.0,
+            jet_ratio: 0.0,
+            noise: 0.0,
+            period: 0.0,
+            delay: DelayLine::new(),
+            dc_x: 0.0,
+            dc_y: 0.0,
+            dc_coefficient: 0.0,
+            attack_coefficient: 0.0,
+            release_coefficient: 0.0,
+            blowing: false,
+            prng: Pcg32::new(27182, 81828 + seed),
+        };
+        jet.set_sample_rate(DEFAULT_SAMPLE_RATE);
+        jet
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+        self.delay
+            .set_max_delay((MAX_JET_DELAY * sample_rate).ceil() as usize);
+        self.dc_coefficient =
+            (-2.0 * std::f32::consts::PI * DC_BLOCKER_FREQUENCY / sample_rate).exp();
+        self.attack_coefficient = 1.0 - (-1.0 / (JET_ATTACK_TIME * sample_rate)).exp();
+        self.release_coefficient = 1.0 - (-1.0 / (JET_RELEASE_TIME * sample_rate)).exp();
+    }
+
+    pub fn reset(&mut self) {
+        self.pressure = 0.0;
+        self.target_pressure = 0.0;
+        self.delay.reset();
+        self.dc_x = 0.0;
+        self.dc_y = 0.0;
+        self.blowing = false;
+    }
+
+    /// Starts blowing, it keeps going until `release()`. `decay` is the decay time of the fundamental
+    /// in seconds, the jet is weaker on longer ringing modes so they don't get louder.
+    pub fn start(&mut self, fundamental: f32, decay: f32) {
+        self.period = self.sample_rate / fundamental;
+        // The force is added every sample, so it has to be scaled to sound the same at all rates
+        self.gain = JET_GAIN * DEFAULT_SAMPLE_RATE / (self.sample_rate * decay);
+        self.blowing = true;
+    }
+
+    pub fn release(&mut self) {
+        self.blowing = false;
+    }
+
+    /// `pressure` and `noise` go from 0.0 to 1.0, `jet_ratio` is the length of the jet relative to
+    /// the period of the fundamental.
+    pub fn set_params(&mut self, pressure: f32, jet_ratio: f32, noise: f32) {
+        self.target_pressure = pressure * MAX_PRESSURE;
+        self.jet_ratio = jet_ratio;
+        self.noise = noise;
+    }
+
+    /// Returns the force of the jet on the resonator, given the output of the resonator.
+    pub fn process(&mut self, resonator_output: f32) -> f32 {
+        let (target, coefficient) = if self.blowing {
+            (self.target_pressure, self.attack_coefficient)
+        } else {
+            (0.0, self.release_coefficient)
+        };
+        self.pressure += (target - self.pressure) * coefficient;
+
+        if !self.blowing && self.pressure < 1e-5 {
+            return 0.0;
+        }
+
+        let breath = self.pressure * (1.0 + self.noise * self.prng.gen_range(-1.0..=1.0));
+        self.delay.write(breath - resonator_output * FEEDBACK_GAIN);
+        let jet = self.delay.read(self.jet_ratio * self.period);
+        let jet = (jet * (jet * jet - 1.0)).clamp(-1.0, 1.0);
+
+        // The pressure itself would push the bar aside, only the oscillation should get through
+        self.dc_y = jet - self.dc_x + self.dc_coefficient * self.dc_y;
+        self.dc_x = jet;
+        self.dc_y * self.gain
+    }
+}
@@ -0,0 +1,265 @@
+use std::sync::Arc;
+
+use crate::{
+    modal_synth::{
+        bell::{BellPartialTuning, BellTuning, build_bell_modes},
+        geometry::{geometry_ratios, prepare_geometries},
+        modes::{Mode, NUM_MODES},
+    },
+    params::{PockyplockyParams, Timbre},
+};
+
+// Modes of geometries fall off in amplitude and decay with this power of their frequency ratio,
+// roughly like a bar struck near its end
+const GEOMETRY_AMPLITUDE_TILT: f32 = 0.5;
+const GEOMETRY_DECAY_TILT: f32 = 0.7;
+
+/// Something that gives the modes of an instrument. Sources read their own params, the
+/// `ModeCalculator` applies size, material, the mode tweaks and the articulation on top.
+pub trait ModeSource: Send {
+    /// The modes for a note, `decay` is the T60 of the fundamental in seconds.
+    fn build_modes(
+        &self,
+        fundamental: f32,
+        velocity: f32,
+        decay: f32,
+        note: u8,
+    ) -> [Mode; NUM_MODES];
+
+    /// How fast every mode beats relative to the Beating Rate, modes beyond these beat at the rate.
+    fn beat_factors(&self) -> &'static [f32] {
+        &[]
+    }
+}
+
+fn bell_tuning(params: &PockyplockyParams) -> BellTuning {
+    BellTuning {
+        hum: BellPartialTuning {
+            cents: params.bell_hum_tuning.value(),
+            level: params.bell_hum_level.value(),
+        },
+        prime: BellPartialTuning {
+            cents: params.bell_prime_tuning.value(),
+            level: params.bell_prime_level.value(),
+        },
+        tierce: BellPartialTuning {
+            cents: params.bell_tierce_tuning.value(),
+            level: params.bell_tierce_level.value(),
+        },
+        quint: BellPartialTuning {
+            cents: params.bell_quint_tuning.value(),
+            level: params.bell_quint_level.value(),
+        },
+        nominal: BellPartialTuning {
+            cents: params.bell_nominal_tuning.value(),
+            level: params.bell_nominal_level.value(),
+        },
+        upper: BellPartialTuning {
+            cents: params.bell_upper_tuning.value(),
+            level: params.bell_upper_level.value(),
+        },
+        warble: params.bell_warble.value(),
+        third: params.bell_third.value(),
+    }
+}
+
+fn timbre_modes(
+    params: &PockyplockyParams,
+    timbre: Timbre,
+    fundamental: f32,
+    decay: f32,
+) -> [Mode; NUM_MODES] {
+    match timbre {
+        Timbre::Bell => build_bell_modes(&bell_tuning(params), fundamental, decay),
+        _ => Timbre::build_modes(timbre, fundamental, decay),
+    }
+}
+
+/// The timbres measured from real instruments, and the bell.
+pub struct TimbreSource {
+    params: Arc<PockyplockyParams>,
+}
+
+impl TimbreSource {
+    pub fn new(params: Arc<PockyplockyParams>) -> Self {
+        Self { params }
+    }
+}
+
+impl ModeSource for TimbreSource {
+    fn build_modes(
+        &self,
+        fundamental: f32,
+        _velocity: f32,
+        decay: f32,
+        _note: u8,
+    ) -> [Mode; NUM_MODES] {
+        timbre_modes(&self.params, self.params.timbre.value(), fundamental, decay)
+    }
+
+    fn beat_factors(&self) -> &'static [f32] {
+        Timbre::beat_factors(self.params.timbre.value())
+    }
+}
+
+/// Blends from the Timbre into the Morph Timbre. Every mode glides from its frequency in one
+/// timbre to the same mode in the other, modes only one of them has fade in.
+pub struct MorphSource {
+    params: Arc<PockyplockyParams>,
+}
+
+impl MorphSource {
+    pub fn new(params: Arc<PockyplockyParams>) -> Self {
+        Self { params }
+    }
+}
+
+impl ModeSource for MorphSource {
+    fn build_modes(
+        &self,
+        fundamental: f32,
+        _velocity: f32,
+        decay: f32,
+        _note: u8,
+    ) -> [Mode; NUM_MODES] {
+        let from = timbre_modes(&self.params, self.params.timbre.value(), fundamental, decay);
+        let to = timbre_modes(
+            &self.params,
+            self.params.morph_timbre.value(),
+            fundamental,
+            decay,
+        );
+        let amount = self.params.morph_amount.value();
+
+        std::array::from_fn(|i| {
+            let (from, to) = (&from[i], &to[i]);
+            let lerp = |a: f32, b: f32| a + (b - a) * amount;
+            // Frequencies and decays glide logarithmically, so the middle sounds like the middle
+            let glide = |a: f32, b: f32| {
+                if a <= 0.0 {
+                    b
+                } else if b <= 0.0 {
+                    a
+                } else {
+                    a * (b / a).powf(amount)
+                }
+            };
+
+            Mode {
+                frequency: glide(from.frequency, to.frequency),
+                amplitude: lerp(from.amplitude, to.amplitude),
+                decay: glide(from.decay, to.decay),
+                aftersound_decay: lerp(from.aftersound_decay, to.aftersound_decay),
+                aftersound_level: lerp(from.aftersound_level, to.aftersound_level),
+                beat_frequency: 0.0,
+                beat_depth: 0.0,
+            }
+        })
+    }
+
+    fn beat_factors(&self) -> &'static [f32] {
+        if self.params.morph_amount.value() < 0.5 {
+            Timbre::beat_factors(self.params.timbre.value())
+        } else {
+            Timbre::beat_factors(self.params.morph_timbre.value())
+        }
+    }
+}
+
+/// The modes of the loaded mode file. The file is borrowed for every note rather than kept, so it
+/// can be replaced on the background thread. Until one is loaded the Timbre plays instead.
+pub struct UserFileSource {
+    params: Arc<PockyplockyParams>,
+}
+
+impl UserFileSource {
+    pub fn new(params: Arc<PockyplockyParams>) -> Self {
+        Self { params }
+    }
+}
+
+impl ModeSource for UserFileSource {
+    fn build_modes(
+        &self,
+        fundamental: f32,
+        _velocity: f32,
+        decay: f32,
+        _note: u8,
+    ) -> [Mode; NUM_MODES] {
+        if let Ok(mode_file) = self.params.mode_file.try_read()
+            && let Some(mode_file) = mode_file.as_ref()
+        {
+            return std::array::from_fn(|i| match mode_file.modes.get(i) {
+                Some(entry) => {
+                    let mode_decay = entry.decay_factor * decay;
+                    Mode {
+                        frequency: entry.ratio * fundamental,
+                        amplitude: entry.amplitude,
+                        decay: mode_decay,
+                        aftersound_decay: entry.aftersound_decay_factor * mode_decay,
+                        aftersound_level: entry.aftersound_level,
+                        ..Mode::silent()
+                    }
+                }
+                None => Mode::silent(),
+            });
+        }
+
+        timbre_modes(&self.params, self.params.timbre.value(), fundamental, decay)
+    }
+
+    // The Timbre that plays until the mode file is loaded beats like it does on its own
+    fn beat_factors(&self) -> &'static [f32] {
+        let is_loaded = self
+            .params
+            .mode_file
+            .try_read()
+            .is_ok_and(|mode_file| mode_file.is_some());
+        if is_loaded {
+            &[]
+        } else {
+            Timbre::beat_factors(self.params.timbre.value())
+        }
+    }
+}
+
+/// Modes computed from the shape of the vibrating object, rather than measured.
+pub struct GeometrySource {
+    params: Arc<PockyplockyParams>,
+}
+
+impl GeometrySource {
+    pub fn new(params: Arc<PockyplockyParams>) -> Self {
+        prepare_geometries();
+        Self { params }
+    }
+}
+
+impl ModeSource for GeometrySource {
+    fn build_modes(
+        &self,
+        fundamental: f32,
+        _velocity: f32,
+        decay: f32,
+        _note: u8,
+    ) -> [Mode; NUM_MODES] {
+        let ratios = geometry_ratios(
+            self.params.geometry.value(),
+            self.params.plate_aspect.value(),
+        );
+
+        std::array::from_fn(|i| {
+            let ratio = ratios[i];
+            if ratio <= 0.0 {
+                return Mode::silent();
+            }
+
+            Mode {
+                frequency: ratio * fundamental,
+                amplitude: ratio.powf(-GEOMETRY_AMPLITUDE_TILT),
+                decay: decay * ratio.powf(-GEOMETRY_DECAY_TILT),
+                ..Mode::silent()
+            }
+        })
+    }
+}
@@ -1,28 +1,84 @@
-use crate::params::{PockyplockyParams, Timbre};
+use crate::{
+    articulation::Articulation,
+    modal_synth::mode_source::{
+        GeometrySource, ModeSource, MorphSource, TimbreSource, UserFileSource,
+    },
+    params::{PockyplockyParams, Timbre},
+};
 use std::sync::Arc;
 
-pub const NUM_MODES: usize = 8;
+pub const NUM_MODES: usize = 24;
+
+// Reference frequency at which the Decay parameter sets the T60 when material damping is used.
+const MATERIAL_REFERENCE_FREQUENCY: f32 = 440.0;
+
+// How much shorter the modes ring with the damping articulations. Muted modes are damped more the
+// higher they are.
+const DEAD_STROKE_DAMPING: f32 = 0.05;
+const MUTED_DAMPING: f32 = 0.25;
+const HARMONICS_DAMPING: f32 = 0.05;
+
+// Modes closer together than this are the two halves of a bell doublet, the widest Bell Warble
+// splits them 5 Hz apart. The finger damps both or neither.
+const DOUBLET_SPACING: f32 = 5.5;
+
+// Amplitude tilt of the modes for rimshots, as a power of their ratio to the fundamental
+const RIMSHOT_TILT: f32 = 0.5;
 
 pub struct Mode {
     pub frequency: f32,
     pub amplitude: f32,
-    pub decay: f32, // T60
+    pub decay: f32,            // T60, of the prompt sound when there is an aftersound
+    pub aftersound_decay: f32, // T60 of the aftersound
+    pub aftersound_level: f32, // Amplitude of the aftersound relative to the mode, 0 for none
+    pub beat_frequency: f32,   // Distance in Hz to the detuned partner of this mode
+    pub beat_depth: f32,       // Amplitude of the partner relative to the mode
+}
+
+impl Mode {
+    pub fn silent() -> Self {
+        Self {
+            frequency: 0.0,
+            amplitude: 0.0,
+            decay: 1.0,
+            aftersound_decay: 0.0,
+            aftersound_level: 0.0,
+            beat_frequency: 0.0,
+            beat_depth: 0.0,
+        }
+    }
+
+    /// How long it takes for this mode to decay by 60dB.
+    pub fn ring_time(&self) -> f32 {
+        if self.aftersound_level > 0.0 {
+            self.decay.max(self.aftersound_decay)
+        } else {
+            self.decay
+        }
+    }
 }
 
+// Timbres can have fewer modes than NUM_MODES, the remaining modes are silent. The beating rate of
+// each mode is scaled by its beat factor, when there are none all modes beat at the same rate.
+// Modes decay in two stages when the timbre has aftersound levels, the decay factors then set the
+// prompt decay and the aftersound decay factors are relative to that.
 struct TimbreData {
-    freq_ratios: [f32; NUM_MODES],
-    amp_factors: [f32; NUM_MODES],
-    decay_factors: [f32; NUM_MODES],
+    freq_ratios: &'static [f32],
+    amp_factors: &'static [f32],
+    decay_factors: &'static [f32],
+    aftersound_decay_factors: &'static [f32],
+    aftersound_levels: &'static [f32],
+    beat_factors: &'static [f32],
 }
 
-const TIMBRE_DATA: [TimbreData; 9] = [
+const TIMBRE_DATA: [TimbreData; 10] = [
     // Xylophone
     TimbreData {
-        freq_ratios: [
+        freq_ratios: &[
             1.0, 3.0075758, 6.007576, 8.969697, 10.25, 10.901515, 11.666667, 12.340909,
         ],
-        amp_factors: [0.560, 0.097, 1.000, 0.279, 0.162, 0.261, 0.127, 0.430],
-        decay_factors: [
+        amp_factors: &[0.560, 0.097, 1.000, 0.279, 0.162, 0.261, 0.127, 0.430],
+        decay_factors: &[
             1.0,
             0.17207472,
             0.081453,
@@ -32,108 +88,223 @@ const TIMBRE_DATA: [TimbreData; 9] = [
             0.05363951,
             0.031945642,
         ],
+        aftersound_decay_factors: &[],
+        aftersound_levels: &[],
+        beat_factors: &[],
     },
     // Xylophone2
     TimbreData {
-        freq_ratios: [
+        freq_ratios: &[
             1.0, 4.115607, 8.132948, 11.497109, 15.202312, 17.086704, 21.624277, 23.820808,
         ],
-        amp_factors: [1.000, 0.101, 0.043, 0.003, 0.005, 0.003, 0.002, 0.005],
-        decay_factors: [
+        amp_factors: &[1.000, 0.101, 0.043, 0.003, 0.005, 0.003, 0.002, 0.005],
+        decay_factors: &[
             1.0, 0.30044422, 0.2148915, 0.33719775, 0.14416045, 0.14055772, 0.13748056, 0.12131587,
         ],
+        aftersound_decay_factors: &[],
+        aftersound_levels: &[],
+        beat_factors: &[],
     },
     // MetalPan
     TimbreData {
-        freq_ratios: [
+        freq_ratios: &[
             1.0, 3.1835206, 5.614232, 9.301498, 12.144195, 11.17603, 23.576779, 26.728464,
         ],
-        amp_factors: [1.000, 0.981, 0.533, 0.169, 0.602, 0.699, 0.135, 0.147],
-        decay_factors: [
+        amp_factors: &[1.000, 0.981, 0.533, 0.169, 0.602, 0.699, 0.135, 0.147],
+        decay_factors: &[
             1.0, 0.7010969, 0.44068757, 0.4480493, 0.23032802, 0.14001636, 0.16176027, 0.10783238,
         ],
+        aftersound_decay_factors: &[],
+        aftersound_levels: &[],
+        beat_factors: &[1.0, 1.3, 0.7, 1.8, 2.2, 0.9, 2.6, 3.1],
     },
     // GlassMarimba
     TimbreData {
-        freq_ratios: [
+        freq_ratios: &[
             1.0, 1.9726688, 2.6623795, 5.226688, 5.4389067, 6.7299037, 8.657557, 9.61254,
         ],
-        amp_factors: [1.000, 0.007, 0.024, 0.011, 0.007, 0.003, 0.001, 0.001],
-        decay_factors: [
+        amp_factors: &[1.000, 0.007, 0.024, 0.011, 0.007, 0.003, 0.001, 0.001],
+        decay_factors: &[
             1.0, 1.1131396, 0.19271448, 0.08029568, 0.4437378, 0.93251647, 1.4748467, 1.072432,
         ],
+        aftersound_decay_factors: &[],
+        aftersound_levels: &[],
+        beat_factors: &[],
     },
     // Piano
     TimbreData {
-        freq_ratios: [1.0, 2.026, 3.097, 4.244, 5.468, 6.807, 8.220, 9.712],
-        amp_factors: [1.00, 0.45, 0.275, 0.15, 0.075, 0.04, 0.035, 0.02],
-        decay_factors: [1.0, 0.4, 0.25, 0.175, 0.125, 0.1, 0.075, 0.05],
+        freq_ratios: &[1.0, 2.026, 3.097, 4.244, 5.468, 6.807, 8.220, 9.712],
+        amp_factors: &[1.00, 0.45, 0.275, 0.15, 0.075, 0.04, 0.035, 0.02],
+        decay_factors: &[1.0, 0.4, 0.25, 0.175, 0.125, 0.1, 0.075, 0.05],
+        aftersound_decay_factors: &[4.0, 3.5, 3.0, 3.0, 2.5, 2.5, 2.0, 2.0],
+        aftersound_levels: &[0.15, 0.12, 0.1, 0.1, 0.08, 0.08, 0.06, 0.05],
+        beat_factors: &[],
     },
     // WoodBlocks
     TimbreData {
-        freq_ratios: [1.0, 3.721393, 6.5771146, 8.9801, 4.0, 8.0, 16.0, 32.0],
-        amp_factors: [1.000, 0.076, 0.0, 0.0, 0.0, 0.0, 0.007, 0.003],
-        decay_factors: [
+        freq_ratios: &[1.0, 3.721393, 6.5771146, 8.9801, 4.0, 8.0, 16.0, 32.0],
+        amp_factors: &[1.000, 0.076, 0.0, 0.0, 0.0, 0.0, 0.007, 0.003],
+        decay_factors: &[
             1.0, 0.6924592, 0.598266, 0.86315242, 0.51367573, 0.6337103, 0.5083179, 0.49256307,
         ],
+        aftersound_decay_factors: &[],
+        aftersound_levels: &[],
+        beat_factors: &[],
     },
     // SteelDrum
     TimbreData {
-        freq_ratios: [
+        freq_ratios: &[
             1.0, 1.9890109, 3.967033, 8.0, 15.989011, 62.89011, 94.79121, 122.65934,
         ],
-        amp_factors: [0.831, 1.000, 0.892, 0.454, 0.335, 0.001, 0.000, 0.000],
-        decay_factors: [
+        amp_factors: &[0.831, 1.000, 0.892, 0.454, 0.335, 0.001, 0.000, 0.000],
+        decay_factors: &[
             1.0, 0.6238995, 0.58501945, 0.4073751, 0.43913596, 0.47025242, 0.45652582, 0.94848394,
         ],
+        aftersound_decay_factors: &[3.0, 2.5, 2.5, 2.0, 2.0, 1.5, 1.5, 1.5],
+        aftersound_levels: &[0.2, 0.15, 0.12, 0.1, 0.1, 0.05, 0.05, 0.05],
+        beat_factors: &[0.6, 1.0, 1.4, 1.1, 2.0, 2.5, 3.0, 3.5],
     },
     // MetalCup
     TimbreData {
-        freq_ratios: [
+        freq_ratios: &[
             1.0, 5.7730673, 8.700748, 13.329177, 20.957606, 24.117207, 31.286783, 35.411472,
         ],
-        amp_factors: [1.000, 0.109, 0.058, 0.031, 0.007, 0.008, 0.012, 0.012],
-        decay_factors: [
+        amp_factors: &[1.000, 0.109, 0.058, 0.031, 0.007, 0.008, 0.012, 0.012],
+        decay_factors: &[
             1.0, 0.46632567, 0.19075759, 0.14047954, 0.22846884, 0.23673304, 0.18408032, 0.16092658,
         ],
+        aftersound_decay_factors: &[],
+        aftersound_levels: &[],
+        beat_factors: &[1.0, 1.7, 2.3, 0.8, 2.9, 3.4, 1.5, 4.0],
     },
     // Cowbell
     TimbreData {
-        freq_ratios: [
+        freq_ratios: &[
             0.99, 2.617788, 5.194327, 7.142597, 9.11625, 11.4548, 17.5057, 29.8047,
         ],
-        amp_factors: [1.000, 0.157, 0.133, 0.041, 0.157, 0.077, 0.059, 0.004],
-        decay_factors: [
+        amp_factors: &[1.000, 0.157, 0.133, 0.041, 0.157, 0.077, 0.059, 0.004],
+        decay_factors: &[
             1.0, 0.479536, 0.987948, 1.087171, 0.457817, 0.545215, 0.449881, 0.324752,
         ],
+        aftersound_decay_factors: &[],
+        aftersound_levels: &[],
+        beat_factors: &[0.5, 1.2, 1.6, 2.1, 0.9, 2.7, 3.2, 3.8],
+    },
+    // Gong
+    TimbreData {
+        freq_ratios: &[
+            1.0, 1.52, 1.97, 2.44, 2.91, 3.37, 3.82, 4.29, 4.76, 5.24, 5.73, 6.2, 6.69, 7.18, 7.71,
+            8.22, 8.75, 9.3, 9.84, 10.41, 10.97, 11.55, 12.14, 12.73,
+        ],
+        amp_factors: &[
+            1.0, 0.7, 0.55, 0.5, 0.4, 0.35, 0.3, 0.28, 0.12, 0.1, 0.09, 0.08, 0.07, 0.06, 0.05,
+            0.05, 0.04, 0.04, 0.03, 0.03, 0.03, 0.02, 0.02, 0.02,
+        ],
+        decay_factors: &[
+            1.0, 0.9, 0.85, 0.8, 0.75, 0.7, 0.65, 0.6, 0.6, 0.58, 0.56, 0.54, 0.52, 0.5, 0.48,
+            0.46, 0.44, 0.42, 0.4, 0.38, 0.36, 0.34, 0.32, 0.3,
+        ],
+        aftersound_decay_factors: &[],
+        aftersound_levels: &[],
+        beat_factors: &[],
     },
 ];
 
+/// Rayleigh damping coefficients, a mode at frequency f decays with T60 = 1 / (a + b·f²).
+struct Material {
+    a: f32,
+    b: f32,
+}
+
+impl Material {
+    /// Interpolates between the material presets, `blend` goes from 0.0 (wood) to 1.0 (steel).
+    fn from_blend(blend: f32) -> Self {
+        let position = blend.clamp(0.0, 1.0) * (MATERIALS.len() - 1) as f32;
+        let index = (position.floor() as usize).min(MATERIALS.len() - 2);
+        let t = position - index as f32;
+        let from = &MATERIALS[index];
+        let to = &MATERIALS[index + 1];
+
+        // The coefficients span orders of magnitude, interpolate them logarithmically
+        Self {
+            a: from.a * (to.a / from.a).powf(t),
+            b: from.b * (to.b / from.b).powf(t),
+        }
+    }
+
+    fn decay_at(&self, frequency: f32) -> f32 {
+        1.0 / (self.a + self.b * frequency * frequency)
+    }
+}
+
+const MATERIALS: [Material; 4] = [
+    // Wood
+    Material { a: 4.0, b: 2.0e-6 },
+    // Plastic
+    Material { a: 2.5, b: 6.0e-7 },
+    // Glass
+    Material { a: 0.8, b: 1.5e-7 },
+    // Steel
+    Material { a: 0.25, b: 2.0e-8 },
+];
+
 impl Timbre {
     pub fn build_modes(timbre: Timbre, fundamental: f32, decay: f32) -> [Mode; NUM_MODES] {
         let data = &TIMBRE_DATA[timbre as usize];
-        std::array::from_fn(|i| Mode {
-            frequency: data.freq_ratios[i] * fundamental,
-            amplitude: data.amp_factors[i],
-            decay: data.decay_factors[i] * decay,
+        std::array::from_fn(|i| {
+            if i < data.freq_ratios.len() {
+                let mode_decay = data.decay_factors[i] * decay;
+                Mode {
+                    frequency: data.freq_ratios[i] * fundamental,
+                    amplitude: data.amp_factors[i],
+                    decay: mode_decay,
+                    aftersound_decay: data.aftersound_decay_factors.get(i).copied().unwrap_or(0.0)
+                        * mode_decay,
+                    aftersound_level: data.aftersound_levels.get(i).copied().unwrap_or(0.0),
+                    beat_frequency: 0.0,
+                    beat_depth: 0.0,
+                }
+            } else {
+                Mode::silent()
+            }
         })
     }
+
+    pub fn beat_factors(timbre: Timbre) -> &'static [f32] {
+        match timbre {
+            Timbre::Bell => &[],
+            _ => TIMBRE_DATA[timbre as usize].beat_factors,
+        }
+    }
 }
 
 pub struct ModeCalculator {
     modes: [Mode; NUM_MODES],
     params: Arc<PockyplockyParams>,
+    sources: Vec<Box<dyn ModeSource>>, // In the order of `ModeSourceKind`
 }
 
 impl ModeCalculator {
     pub fn new(params: Arc<PockyplockyParams>) -> Self {
+        let sources: Vec<Box<dyn ModeSource>> = vec![
+            Box::new(TimbreSource::new(params.clone())),
+            Box::new(MorphSource::new(params.clone())),
+            Box::new(UserFileSource::new(params.clone())),
+            Box::new(GeometrySource::new(params.clone())),
+        ];
+
         Self {
             modes: std::array::from_fn(|_| Mode {
                 frequency: 0.0,
                 amplitude: 0.0,
                 decay: 0.0,
+                aftersound_decay: 0.0,
+                aftersound_level: 0.0,
+                beat_frequency: 0.0,
+                beat_depth: 0.0,
             }),
             params,
+            sources,
         }
     }
 
@@ -142,28 +313,118 @@ impl ModeCalculator {
     }
 
     #[allow(clippy::needless_range_loop)]
-    pub fn set_frequency(&mut self, fundamental_freq: f32, decay: f32) {
-        let timbre = self.params.timbre.value();
-        let new_modes = Timbre::build_modes(timbre, fundamental_freq, decay);
+    pub fn set_frequency(
+        &mut self,
+        fundamental_freq: f32,
+        velocity: f32,
+        decay: f32,
+        note: u8,
+        articulation: Articulation,
+    ) {
+        let source = &self.sources[self.params.mode_source.value() as usize];
+        let size = self.params.size.value();
+        let mut new_modes =
+            source.build_modes(fundamental_freq / size, velocity, decay * size, note);
+
+        if self.params.material_enabled.value() {
+            let material = Material::from_blend(self.params.material.value());
+            let reference_decay = material.decay_at(MATERIAL_REFERENCE_FREQUENCY);
+            for mode in &mut new_modes {
+                let material_decay =
+                    decay * size * material.decay_at(mode.frequency) / reference_decay;
+                mode.aftersound_decay *= material_decay / mode.decay;
+                mode.decay = material_decay;
+            }
+        }
 
         let fundamental_balance = self.params.fundamental_balance.value();
         let sparkle = self.params.sparkle.value();
 
         self.modes[0].frequency = new_modes[0].frequency;
         self.modes[0].decay = new_modes[0].decay;
+        self.modes[0].aftersound_decay = new_modes[0].aftersound_decay;
+        self.modes[0].aftersound_level = new_modes[0].aftersound_level;
         self.modes[0].amplitude = new_modes[0].amplitude * (1.0 + fundamental_balance);
 
         for i in 1..NUM_MODES {
             if new_modes[i].frequency > 20000.0 {
                 self.modes[i].frequency = 20000.0;
                 self.modes[i].decay = 1.0;
+                self.modes[i].aftersound_level = 0.0;
                 self.modes[i].amplitude = 0.0;
             } else {
                 self.modes[i].frequency = new_modes[i].frequency;
                 self.modes[i].decay = new_modes[i].decay * (1.0 + sparkle);
+                self.modes[i].aftersound_decay = new_modes[i].aftersound_decay * (1.0 + sparkle);
+                self.modes[i].aftersound_level = new_modes[i].aftersound_level;
                 self.modes[i].amplitude = new_modes[i].amplitude * (1.0 - fundamental_balance);
             }
         }
+
+        let beating_rate = self.params.beating_rate.value();
+        let beating_depth = self.params.beating_depth.value();
+        let beat_factors = source.beat_factors();
+        for i in 0..NUM_MODES {
+            let beat_factor = beat_factors.get(i).copied().unwrap_or(1.0);
+            self.modes[i].beat_frequency = beating_rate * beat_factor;
+            self.modes[i].beat_depth = beating_depth;
+        }
+
+        self.apply_articulation(fundamental_freq, articulation);
+    }
+
+    fn apply_articulation(&mut self, fundamental_freq: f32, articulation: Articulation) {
+        match articulation {
+            Articulation::Normal | Articulation::Roll => {}
+            Articulation::DeadStroke => {
+                for mode in &mut self.modes {
+                    mode.decay *= DEAD_STROKE_DAMPING;
+                    mode.aftersound_level = 0.0;
+                }
+            }
+            Articulation::Muted => {
+                for mode in &mut self.modes {
+                    let ratio = (mode.frequency / fundamental_freq).max(1.0);
+                    mode.decay *= MUTED_DAMPING / ratio;
+                    mode.aftersound_decay *= MUTED_DAMPING / ratio;
+                }
+            }
+            Articulation::Rimshot => {
+                for mode in &mut self.modes {
+                    let ratio = (mode.frequency / fundamental_freq).max(1.0);
+                    mode.amplitude *= ratio.powf(RIMSHOT_TILT);
+                }
+            }
+            Articulation::Harmonics => {
+                // Timbres and mode files don't list their modes in order, so they're ranked by
+                // frequency first. On a bar every other one has an antinode at the centre and gets
+                // damped by the finger.
+                let mut order: [usize; NUM_MODES] = std::array::from_fn(|i| i);
+                order.sort_unstable_by(|&a, &b| {
+                    self.modes[a].frequency.total_cmp(&self.modes[b].frequency)
+                });
+
+                let mut rank = 0;
+                let mut previous_frequency = None;
+                for i in order {
+                    let mode = &mut self.modes[i];
+                    if mode.frequency <= 0.0 {
+                        continue;
+                    }
+                    if previous_frequency
+                        .is_some_and(|previous| mode.frequency - previous > DOUBLET_SPACING)
+                    {
+                        rank += 1;
+                    }
+                    previous_frequency = Some(mode.frequency);
+
+                    if rank % 2 == 0 {
+                        mode.decay *= HARMONICS_DAMPING;
+                        mode.aftersound_level = 0.0;
+                    }
+                }
+            }
+        }
     }
 
     pub fn reset(&mut self) {
@@ -171,6 +432,10 @@ impl ModeCalculator {
             mode.frequency = 0.0;
             mode.amplitude = 0.0;
             mode.decay = 0.0;
+            mode.aftersound_decay = 0.0;
+            mode.aftersound_level = 0.0;
+            mode.beat_frequency = 0.0;
+            mode.beat_depth = 0.0;
         }
     }
 }
@@ -0,0 +1,104 @@
+use rand::Rng;
+use rand_pcg::Pcg32;
+
+use crate::params::NoiseColour;
+
+/// White, pink or brown noise. Pink noise uses Paul Kellet's filter, brown noise is leaky
+/// integrated white noise. Both are scaled to roughly the level of the white noise.
+pub struct Noise {
+    prng: Pcg32,
+    pink: [f32; 7],
+    brown: f32,
+}
+
+impl Noise {
+    pub fn new(prng: Pcg32) -> Self {
+        Self {
+            prng,
+            pink: [0.0; 7],
+            brown: 0.0,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.pink = [0.0; 7];
+        self.brown = 0.0;
+    }
+
+    pub fn next(&mut self, colour: NoiseColour) -> f32 {
+        let white = self.prng.gen_range(-1.0..=1.0);
+        match colour {
+            NoiseColour::White => white,
+            NoiseColour::Pink => {
+                let b = &mut self.pink;
+                b[0] = 0.99886 * b[0] + white * 0.0555179;
+                b[1] = 0.99332 * b[1] + white * 0.0750759;
+                b[2] = 0.969 * b[2] + white * 0.153852;
+                b[3] = 0.8665 * b[3] + white * 0.3104856;
+                b[4] = 0.55 * b[4] + white * 0.5329522;
+                b[5] = -0.7616 * b[5] - white * 0.016898;
+                let pink = b[0] + b[1] + b[2] + b[3] + b[4] + b[5] + b[6] + white * 0.5362;
+                b[6] = white * 0.115926;
+                pink * 0.11
+            }
+            NoiseColour::Brown => {
+                self.brown = (self.brown + 0.02 * white) / 1.02;
+                self.brown * 3.5
+            }
+        }
+    }
+}
+
+/// Band-pass filter with a peak gain of 0 dB, from the Audio EQ Cookbook.
+pub struct BandPass {
+    b0: f32,
+    b2: f32,
+    a1: f32,
+    a2: f32,
+    x1: f32,
+    x2: f32,
+    y1: f32,
+    y2: f32,
+}
+
+impl BandPass {
+    pub fn new() -> Self {
+        Self {
+            b0: 0.0,
+            b2: 0.0,
+            a1: 0.0,
+            a2: 0.0,
+            x1: 0.0,
+            x2: 0.0,
+            y1: 0.0,
+            y2: 0.0,
+        }
+    }
+
+    pub fn set(&mut self, frequency: f32, q: f32, sample_rate: f32) {
+        let omega = 2.0 * std::f32::consts::PI * frequency.min(sample_rate * 0.45) / sample_rate;
+        let (sin, cos) = omega.sin_cos();
+        let alpha = sin / (2.0 * q);
+        let a0 = 1.0 + alpha;
+        self.b0 = alpha / a0;
+        self.b2 = -alpha / a0;
+        self.a1 = -2.0 * cos / a0;
+        self.a2 = (1.0 - alpha) / a0;
+    }
+
+    pub fn reset(&mut self) {
+        self.x1 = 0.0;
+        self.x2 = 0.0;
+        self.y1 = 0.0;
+        self.y2 = 0.0;
+    }
+
+    pub fn process(&mut self, input: f32) -> f32 {
+        let output = self.b0 * input + self.b2 * self.x2 - self.a1 * self.y1 - self.a2 * self.y2;
+        self.x2 = self.x1;
+        self.x1 = input;
+        self.y2 = self.y1;
+        self.y1 = output;
+        output
+    }
+}
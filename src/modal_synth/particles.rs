@@ -0,0 +1,204 @@
+use rand::Rng;
+use rand_pcg::Pcg32;
+use std::sync::Arc;
+
+use crate::{
+    constants::DEFAULT_SAMPLE_RATE,
+    modal_synth::{
+        exciter_source::{EnvelopeShape, ExciterSource, Strike},
+        resonator::T60_DECAY_FACTOR,
+    },
+    params::{ParamBuffers, ParticleGesture, PockyplockyParams},
+};
+
+// Collisions happen with a probability of the number of particles out of this, every sample at the
+// default sample rate
+const COLLISION_CHANCES: f32 = 1024.0;
+
+// How long the sound of a single collision lasts, in seconds
+const COLLISION_TIME: f32 = 0.001;
+
+// Brings the collisions down to roughly the level of the other exciters
+const PARTICLE_GAIN: f32 = 0.03;
+
+// Every ridge of a scrape only adds part of the energy of a full shake
+const SCRAPE_ENERGY: f32 = 0.5;
+
+/// A stochastic particle model (PhISEM) of the beads in a shaker or the seeds in a rainstick. A
+/// shake gives the particles energy, which slowly drains away. While there is energy, particles
+/// randomly collide with the shell, every collision adds a short burst of noise. Scraping keeps
+/// adding energy at a steady rate, like a stick running over the ridges of a guiro.
+pub struct Particles {
+    sample_rate: f32,
+    energy: f32,
+    sound_level: f32,
+    system_decay: f32,
+    sound_decay: f32,
+    collision_probability: f32,
+    gain: f32,
+    scraping: bool,
+    scrape_energy: f32,
+    scrape_phase: f32,
+    scrape_increment: f32,
+    prng: Pcg32,
+}
+
+impl Particles {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            sample_rate: DEFAULT_SAMPLE_RATE,
+            energy: 0.0,
+            sound_level: 0.0,
+            system_decay: 0.0,
+            sound_decay: (-1.0 / (COLLISION_TIME * DEFAULT_SAMPLE_RATE)).exp(),
+            collision_probability: 0.0,
+            gain: 0.0,
+            scraping: false,
+            scrape_energy: 0.0,
+            scrape_phase: 0.0,
+            scrape_increment: 0.0,
+            prng: Pcg32::new(31415, 92653 + seed),
+        }
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+        self.sound_decay = (-1.0 / (COLLISION_TIME * sample_rate)).exp();
+    }
+
+    pub fn reset(&mut self) {
+        self.energy = 0.0;
+        self.sound_level = 0.0;
+        self.scraping = false;
+    }
+
+    /// `decay_ms` is the time it takes for the energy of a shake to die out.
+    pub fn set_params(&mut self, count: f32, decay_ms: f32, scrape_rate: f32) {
+        self.system_decay = (T60_DECAY_FACTOR / (decay_ms * 0.001 * self.sample_rate)).exp();
+        self.collision_probability =
+            (count / COLLISION_CHANCES * DEFAULT_SAMPLE_RATE / self.sample_rate).min(1.0);
+        // Keeps the loudness about the same for any number of particles, from the Synthesis
+        // ToolKit's shakers
+        self.gain = count.ln().max(1.0) / 4.0f32.ln() * 40.0 / count * PARTICLE_GAIN;
+        self.scrape_increment = scrape_rate / self.sample_rate;
+    }
+
+    pub fn shake(&mut self, energy: f32) {
+        self.energy += energy;
+    }
+
+    /// Keeps scraping until `release()`, the first ridge is hit right away.
+    pub fn start_scrape(&mut self, energy: f32) {
+        self.scraping = true;
+        self.scrape_energy = energy * SCRAPE_ENERGY;
+        self.scrape_phase = 1.0;
+    }
+
+    pub fn release(&mut self) {
+        self.scraping = false;
+    }
+
+    pub fn process(&mut self) -> f32 {
+        if self.scraping {
+            self.scrape_phase += self.scrape_increment;
+            if self.scrape_phase >= 1.0 {
+                self.scrape_phase -= 1.0;
+                self.energy += self.scrape_energy;
+            }
+        }
+
+        if self.energy < 1e-6 && self.sound_level < 1e-6 {
+            return 0.0;
+        }
+
+        self.energy *= self.system_decay;
+        if self.prng.r#gen::<f32>() < self.collision_probability {
+            self.sound_level += self.gain * self.energy;
+        }
+        let output = self.sound_level * self.prng.gen_range(-1.0..=1.0);
+        self.sound_level *= self.sound_decay;
+        output
+    }
+}
+
+/// The particles, shaken or scraped by every note.
+pub struct ParticleShaker {
+    params: Arc<PockyplockyParams>,
+    particles: Particles,
+}
+
+impl ParticleShaker {
+    pub fn new(params: Arc<PockyplockyParams>, seed: u64) -> Self {
+        Self {
+            params,
+            particles: Particles::new(seed),
+        }
+    }
+}
+
+impl ExciterSource for ParticleShaker {
+    fn enabled(&self) -> bool {
+        self.params.particles_enabled.value()
+    }
+
+    fn level(&self) -> f32 {
+        self.params.particle_level.value()
+    }
+
+    fn velocity_sensitivity(&self) -> f32 {
+        self.params.particle_velocity_sensitivity.value()
+    }
+
+    fn envelope(&self) -> Option<EnvelopeShape> {
+        let params = &self.params;
+        params.particle_envelope.value().then(|| EnvelopeShape {
+            attack_ms: params.particle_envelope_attack.value(),
+            attack_curve: params.particle_envelope_attack_shape.value(),
+            decay_ms: params.particle_envelope_decay.value(),
+            decay_curve: params.particle_envelope_decay_shape.value(),
+        })
+    }
+
+    fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.particles.set_sample_rate(sample_rate);
+    }
+
+    fn reset(&mut self) {
+        self.particles.reset();
+    }
+
+    /// Keeps going until the particles have settled.
+    fn start(&mut self, _fundamental: f32, velocity: f32) -> f32 {
+        let energy = velocity.sqrt() * self.params.particle_energy.value();
+        match self.params.particle_gesture.value() {
+            ParticleGesture::Shake => self.particles.shake(energy),
+            ParticleGesture::Scrape => self.particles.start_scrape(energy),
+        }
+        self.params.particle_decay.value() * 0.001
+    }
+
+    fn release(&mut self) {
+        self.particles.release();
+    }
+
+    fn shake(&mut self, strength: f32) {
+        self.particles
+            .shake(strength * self.params.particle_energy.value());
+    }
+
+    fn process_block(
+        &mut self,
+        _strikes: &[Strike],
+        _param_buffers: &ParamBuffers,
+        output: &mut [f32],
+    ) {
+        self.particles.set_params(
+            self.params.particle_count.value(),
+            self.params.particle_decay.value(),
+            self.params.scrape_rate.value(),
+        );
+        for sample in output.iter_mut() {
+            *sample = self.particles.process();
+        }
+    }
+}
@@ -0,0 +1,48 @@
+use crate::constants::DEFAULT_SAMPLE_RATE;
+
+// The pitch has settled to within 1% of the starting offset after the envelope time
+const SETTLE_FACTOR: f32 = -4.6; // ln(0.01)
+
+/// Starts a note at an offset from its pitch and glides back exponentially.
+pub struct PitchEnvelope {
+    sample_rate: f32,
+    semitones: f32,
+    coefficient: f32,
+}
+
+impl PitchEnvelope {
+    pub fn new() -> Self {
+        Self {
+            sample_rate: DEFAULT_SAMPLE_RATE,
+            semitones: 0.0,
+            coefficient: 0.0,
+        }
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+    }
+
+    pub fn reset(&mut self) {
+        self.semitones = 0.0;
+    }
+
+    pub fn start(&mut self, amount: f32, time_ms: f32) {
+        self.semitones = amount;
+        self.coefficient = (SETTLE_FACTOR / (time_ms * 0.001 * self.sample_rate)).exp();
+    }
+
+    /// Returns the pitch ratio for the next block.
+    pub fn next_block(&mut self, block_len: usize) -> f32 {
+        if self.semitones == 0.0 {
+            return 1.0;
+        }
+
+        let ratio = (self.semitones / 12.0).exp2();
+        self.semitones *= self.coefficient.powi(block_len as i32);
+        if self.semitones.abs() < 1e-4 {
+            self.semitones = 0.0;
+        }
+        ratio
+    }
+}
@@ -5,78 +5,354 @@ use crate::{
 use wide::f32x8;
 
 pub const T60_DECAY_FACTOR: f32 = -6.91; // -ln(1000) for 60dB decay
+
+// The modes are processed 8 at a time
+const NUM_LANES: usize = NUM_MODES / 8;
+
+fn to_lanes(values: &[f32; NUM_MODES]) -> [f32x8; NUM_LANES] {
+    std::array::from_fn(|lane| {
+        let mut chunk = [0.0; 8];
+        chunk.copy_from_slice(&values[lane * 8..(lane + 1) * 8]);
+        f32x8::from(chunk)
+    })
+}
+
+// Modes are kept below this angular frequency when their pitch is raised
+const MAX_OMEGA: f32 = 0.98 * std::f32::consts::PI;
+
+// Maximum pitch increase caused by tension modulation, relative to the mode frequency
+const TENSION_RANGE: f32 = 0.06;
+
+// Release time of the amplitude follower that drives tension modulation
+const TENSION_RELEASE_TIME: f32 = 0.05;
+
+// Strength of the coupling from the low modes into the higher modes at full shimmer, and how much
+// of their energy the low modes lose to it. Both are per sample at the default sample rate.
+const SHIMMER_GAIN: f32 = 20.0;
+const SHIMMER_DRAIN: f32 = 0.0005;
+
+/// A bank of resonators, one per mode. Every resonator is a complex one-pole filter: a phasor that
+/// rotates by the mode frequency and shrinks by the decay every sample. Unlike a two-pole filter,
+/// its frequency can change while it is ringing without affecting its amplitude or phase.
+struct ResonatorBank {
+    omegas: [f32; NUM_MODES],
+    radii: [f32; NUM_MODES],
+    cos: [f32x8; NUM_LANES],
+    sin: [f32x8; NUM_LANES],
+    gains: [f32x8; NUM_LANES],
+    re: [f32x8; NUM_LANES],
+    im: [f32x8; NUM_LANES],
+    amplitudes: [f32x8; NUM_LANES],
+    contact_weights: [f32x8; NUM_LANES],
+    active_lanes: usize,
+}
+
+impl ResonatorBank {
+    fn new() -> Self {
+        Self {
+            omegas: [0.0; NUM_MODES],
+            radii: [0.0; NUM_MODES],
+            cos: [f32x8::ZERO; NUM_LANES],
+            sin: [f32x8::ZERO; NUM_LANES],
+            gains: [f32x8::ZERO; NUM_LANES],
+            re: [f32x8::ZERO; NUM_LANES],
+            im: [f32x8::ZERO; NUM_LANES],
+            amplitudes: [f32x8::ZERO; NUM_LANES],
+            contact_weights: [f32x8::ZERO; NUM_LANES],
+            active_lanes: 0,
+        }
+    }
+
+    fn set_modes(
+        &mut self,
+        frequencies: &[f32; NUM_MODES],
+        amplitudes: &[f32; NUM_MODES],
+        decays: &[f32; NUM_MODES],
+        sample_rate_inv: f32,
+    ) {
+        let omega_factor = 2.0 * std::f32::consts::PI * sample_rate_inv;
+        let decay_factor = T60_DECAY_FACTOR * sample_rate_inv;
+
+        for i in 0..NUM_MODES {
+            self.omegas[i] = omega_factor * frequencies[i];
+            self.radii[i] = (decay_factor / decays[i]).exp();
+        }
+
+        self.amplitudes = to_lanes(amplitudes);
+        self.set_pitch(1.0);
+
+        // Lanes after the last audible mode don't need to be processed
+        self.active_lanes = amplitudes
+            .iter()
+            .rposition(|&amplitude| amplitude != 0.0)
+            .map_or(0, |last| last / 8 + 1);
+    }
+
+    /// Scales the frequencies of all modes by `ratio`.
+    fn set_pitch(&mut self, ratio: f32) {
+        let mut cos = [0.0; NUM_MODES];
+        let mut sin = [0.0; NUM_MODES];
+        let mut gains = [0.0; NUM_MODES];
+
+        for i in 0..NUM_MODES {
+            let omega = (self.omegas[i] * ratio).min(MAX_OMEGA);
+            let (omega_sin, omega_cos) = omega.sin_cos();
+            cos[i] = self.radii[i] * omega_cos;
+            sin[i] = self.radii[i] * omega_sin;
+
+            // Matches the gain of a two-pole resonator with an input gain of f / fs
+            gains[i] = if omega_sin > 1e-6 {
+                omega / (2.0 * std::f32::consts::PI * omega_sin)
+            } else {
+                0.0
+            };
+        }
+
+        self.cos = to_lanes(&cos);
+        self.sin = to_lanes(&sin);
+        self.gains = to_lanes(&gains);
+    }
+
+    fn process_lane(&mut self, lane: usize, input_vec: f32x8, contact_vec: f32x8) -> f32x8 {
+        let re_vec = self.cos[lane] * self.re[lane] - self.sin[lane] * self.im[lane]
+            + self.gains[lane] * (input_vec + self.contact_weights[lane] * contact_vec);
+        let im_vec = self.sin[lane] * self.re[lane] + self.cos[lane] * self.im[lane];
+
+        // Update state
+        self.re[lane] = re_vec;
+        self.im[lane] = im_vec;
+
+        // Multiply by amplitudes
+        im_vec * self.amplitudes[lane]
+    }
+
+    /// The input excites all modes equally, the contact input is weighted by how much every mode
+    /// moves at the contact point.
+    fn process(&mut self, input_vec: f32x8, contact_vec: f32x8) -> f32x8 {
+        let mut result_vec = f32x8::ZERO;
+
+        for lane in 0..self.active_lanes {
+            result_vec += self.process_lane(lane, input_vec, contact_vec);
+        }
+
+        result_vec
+    }
+
+    /// Like `process()`, but the lowest 8 modes drive all modes above them through a quadratic
+    /// nonlinearity, losing some of their own energy in the process. The coupling only goes upwards,
+    /// so this can't become unstable.
+    fn process_coupled(&mut self, input_vec: f32x8, contact_vec: f32x8, coupling: f32) -> f32x8 {
+        let mut result_vec = self.process_lane(0, input_vec, contact_vec);
+
+        let low = result_vec.reduce_add();
+        let drive = coupling * low * low.abs();
+        let drain = f32x8::splat(1.0 - (coupling * low.abs() * SHIMMER_DRAIN).min(0.5));
+        self.re[0] *= drain;
+        self.im[0] *= drain;
+
+        let coupled_input_vec = input_vec + f32x8::splat(drive);
+        for lane in 1..self.active_lanes {
+            result_vec += self.process_lane(lane, coupled_input_vec, contact_vec);
+        }
+
+        result_vec
+    }
+
+    /// Velocity of the modes at the contact point. The quadrature part of every phasor is used,
+    /// which is the velocity divided by the mode frequency.
+    fn contact_velocity(&self) -> f32 {
+        let mut velocity_vec = f32x8::ZERO;
+        for lane in 0..self.active_lanes {
+            velocity_vec += self.re[lane] * self.amplitudes[lane] * self.contact_weights[lane];
+        }
+        velocity_vec.reduce_add()
+    }
+
+    fn reset(&mut self) {
+        self.re = [f32x8::ZERO; NUM_LANES];
+        self.im = [f32x8::ZERO; NUM_LANES];
+    }
+}
+
+/// Renders the modes. Every mode can have a partner, which is rendered by a second bank of
+/// resonators. A slightly detuned partner beats against the mode like the nearly identical mode
+/// pairs found in metal bars and bells. The aftersound of modes that decay in two stages is
+/// rendered by a third bank, at the frequency of the mode and with a longer decay time.
+///
+/// The frequencies of the modes follow the pitch ratio set for every block. With tension modulation
+/// they also rise with the amplitude of the output, like the skin of a drum that is hit hard.
+///
+/// Exciters that touch the bar, like a bow, push it at a contact point and read back how fast it
+/// moves there. Every mode is weighted by how much it moves at that point.
+///
+/// Shimmer couples the lowest modes to the higher ones, so the higher modes keep building up after
+/// the strike like they do on gongs and cymbals. The coupling fades in over the bloom time.
 pub struct ModalResonator {
-    b0: [f32; NUM_MODES],
-    a1: [f32; NUM_MODES],
-    a2: [f32; NUM_MODES],
-    y1: [f32; NUM_MODES],
-    y2: [f32; NUM_MODES],
-    amplitudes: [f32; NUM_MODES],
+    modes: ResonatorBank,
+    partners: ResonatorBank,
+    has_partners: bool,
+    aftersound: ResonatorBank,
+    has_aftersound: bool,
     sample_rate_inv: f32,
-    omega_factor: f32,
-    decay_factor: f32,
+    tension: f32,
+    amplitude: f32,
+    amplitude_release: f32,
+    pitch_ratio: f32,
+    coupling: f32,
+    coupling_target: f32,
+    bloom_coefficient: f32,
 }
 
 impl ModalResonator {
     pub fn new() -> Self {
-        let sample_rate_inv = 1.0 / DEFAULT_SAMPLE_RATE;
         Self {
-            b0: [0.0; NUM_MODES],
-            a1: [0.0; NUM_MODES],
-            a2: [0.0; NUM_MODES],
-            y1: [0.0; NUM_MODES],
-            y2: [0.0; NUM_MODES],
-            amplitudes: [0.0; NUM_MODES],
-            sample_rate_inv,
-            omega_factor: 2.0 * std::f32::consts::PI * sample_rate_inv,
-            decay_factor: T60_DECAY_FACTOR * sample_rate_inv,
+            modes: ResonatorBank::new(),
+            partners: ResonatorBank::new(),
+            has_partners: false,
+            aftersound: ResonatorBank::new(),
+            has_aftersound: false,
+            sample_rate_inv: 1.0 / DEFAULT_SAMPLE_RATE,
+            tension: 0.0,
+            amplitude: 0.0,
+            amplitude_release: (T60_DECAY_FACTOR / (TENSION_RELEASE_TIME * DEFAULT_SAMPLE_RATE))
+                .exp(),
+            pitch_ratio: 1.0,
+            coupling: 0.0,
+            coupling_target: 0.0,
+            bloom_coefficient: 0.0,
         }
     }
 
-    #[allow(clippy::needless_range_loop)]
     pub fn set_modes(&mut self, modes: &[Mode; NUM_MODES]) {
-        for i in 0..NUM_MODES {
-            let omega = self.omega_factor * modes[i].frequency;
-            let r = (self.decay_factor / modes[i].decay).exp();
-            self.a1[i] = -2.0 * r * omega.cos();
-            self.a2[i] = r * r;
-            self.b0[i] = modes[i].frequency * self.sample_rate_inv;
-            self.amplitudes[i] = modes[i].amplitude;
+        self.modes.set_modes(
+            &modes.each_ref().map(|mode| mode.frequency),
+            &modes
+                .each_ref()
+                .map(|mode| mode.amplitude * (1.0 - mode.aftersound_level)),
+            &modes.each_ref().map(|mode| mode.decay),
+            self.sample_rate_inv,
+        );
+
+        // The partner beats against the prompt sound, the aftersound rings on at the frequency of
+        // the mode so it only beats when the partner does
+        let partner_amplitudes = modes
+            .each_ref()
+            .map(|mode| mode.amplitude * (1.0 - mode.aftersound_level) * mode.beat_depth);
+        self.partners.set_modes(
+            &modes
+                .each_ref()
+                .map(|mode| mode.frequency + mode.beat_frequency),
+            &partner_amplitudes,
+            &modes.each_ref().map(|mode| mode.decay),
+            self.sample_rate_inv,
+        );
+        self.has_partners = partner_amplitudes.iter().any(|&amplitude| amplitude != 0.0);
+
+        let aftersound_amplitudes = modes
+            .each_ref()
+            .map(|mode| mode.amplitude * mode.aftersound_level);
+        self.aftersound.set_modes(
+            &modes.each_ref().map(|mode| mode.frequency),
+            &aftersound_amplitudes,
+            &modes.each_ref().map(|mode| mode.aftersound_decay),
+            self.sample_rate_inv,
+        );
+        self.has_aftersound = aftersound_amplitudes
+            .iter()
+            .any(|&amplitude| amplitude != 0.0);
+        self.pitch_ratio = 1.0;
+        self.coupling = 0.0;
+    }
+
+    /// Sets the amount of nonlinear coupling from 0.0 to 1.0, and the time it takes to build up.
+    pub fn set_shimmer(&mut self, shimmer: f32, bloom_time_ms: f32) {
+        // Per sample quantities need to be scaled to keep the sound the same at all sample rates
+        let sample_rate = 1.0 / self.sample_rate_inv;
+        self.coupling_target = shimmer * SHIMMER_GAIN * DEFAULT_SAMPLE_RATE * self.sample_rate_inv;
+        self.bloom_coefficient = 1.0 - (-1.0 / (bloom_time_ms * 0.001 * sample_rate)).exp();
+    }
+
+    /// Sets the contact point, from 0.0 at the end of the bar to 0.5 at the centre. The modes are
+    /// assumed to be shaped like the harmonics of a string, which is close enough for the lower
+    /// modes of a bar.
+    pub fn set_contact_position(&mut self, position: f32) {
+        let weights = to_lanes(&std::array::from_fn(|i| {
+            (std::f32::consts::PI * (i + 1) as f32 * position).sin()
+        }));
+        self.modes.contact_weights = weights;
+        self.partners.contact_weights = weights;
+        self.aftersound.contact_weights = weights;
+    }
+
+    pub fn contact_velocity(&self) -> f32 {
+        let mut velocity = self.modes.contact_velocity();
+        if self.has_partners {
+            velocity += self.partners.contact_velocity();
+        }
+        if self.has_aftersound {
+            velocity += self.aftersound.contact_velocity();
         }
+        velocity
     }
 
-    pub fn process(&mut self, input: f32) -> f32 {
-        let input_vec = f32x8::splat(input);
-        let b0_vec = f32x8::from(self.b0);
-        let a1_vec = f32x8::from(self.a1);
-        let a2_vec = f32x8::from(self.a2);
-        let y1_vec = f32x8::from(self.y1);
-        let y2_vec = f32x8::from(self.y2);
-        let amp_vec = f32x8::from(self.amplitudes);
+    pub fn set_tension(&mut self, tension: f32) {
+        self.tension = tension;
+    }
 
-        let y_vec = b0_vec * input_vec - a1_vec * y1_vec - a2_vec * y2_vec;
+    /// Sets the pitch of the modes relative to their frequencies, meant to be called once per
+    /// block. Tension modulation is applied on top of this.
+    pub fn set_pitch(&mut self, ratio: f32) {
+        let pitch_ratio = ratio * (1.0 + self.tension * TENSION_RANGE * self.amplitude.min(1.0));
 
-        // Update state
-        let new_y2 = y1_vec;
-        let new_y1 = y_vec;
+        if pitch_ratio != self.pitch_ratio {
+            self.pitch_ratio = pitch_ratio;
+            self.modes.set_pitch(pitch_ratio);
+            if self.has_partners {
+                self.partners.set_pitch(pitch_ratio);
+            }
+            if self.has_aftersound {
+                self.aftersound.set_pitch(pitch_ratio);
+            }
+        }
+    }
+
+    pub fn process(&mut self, input: f32) -> f32 {
+        self.process_with_contact(input, 0.0)
+    }
 
-        // Store back to arrays
-        self.y2 = new_y2.into();
-        self.y1 = new_y1.into();
+    /// Like `process()`, with an extra input at the contact point.
+    pub fn process_with_contact(&mut self, input: f32, contact: f32) -> f32 {
+        let input_vec = f32x8::splat(input);
+        let contact_vec = f32x8::splat(contact);
+        let mut result_vec = if self.coupling_target > 0.0 {
+            self.coupling += (self.coupling_target - self.coupling) * self.bloom_coefficient;
+            self.modes
+                .process_coupled(input_vec, contact_vec, self.coupling)
+        } else {
+            self.modes.process(input_vec, contact_vec)
+        };
+
+        if self.has_partners {
+            result_vec += self.partners.process(input_vec, contact_vec);
+        }
+        if self.has_aftersound {
+            result_vec += self.aftersound.process(input_vec, contact_vec);
+        }
 
-        // Multiply by amplitudes and sum
-        let result_vec = y_vec * amp_vec;
-        result_vec.reduce_add()
+        let output = result_vec.reduce_add();
+        self.amplitude = output.abs().max(self.amplitude * self.amplitude_release);
+        output
     }
 
     pub fn reset(&mut self) {
-        self.y1.fill(0.0);
-        self.y2.fill(0.0);
+        self.modes.reset();
+        self.partners.reset();
+        self.aftersound.reset();
+        self.amplitude = 0.0;
     }
 
     pub fn set_sample_rate(&mut self, sample_rate: f32) {
         self.sample_rate_inv = 1.0 / sample_rate;
-        self.omega_factor = 2.0 * std::f32::consts::PI * self.sample_rate_inv;
-        self.decay_factor = T60_DECAY_FACTOR * self.sample_rate_inv;
+        self.amplitude_release = (T60_DECAY_FACTOR / (TENSION_RELEASE_TIME * sample_rate)).exp();
     }
 }
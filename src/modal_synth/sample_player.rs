@@ -0,0 +1,162 @@
+use nih_plug::util;
+use std::sync::Arc;
+
+use crate::{
+    constants::DEFAULT_SAMPLE_RATE,
+    modal_synth::exciter_source::{EnvelopeShape, ExciterSource, Strike},
+    params::{ParamBuffers, PockyplockyParams},
+    sample_kit::SampleKit,
+};
+
+/// Plays a layer of the sample kit once. The kit is borrowed for every call rather than kept, so it
+/// can be replaced on the background thread while a note is playing.
+pub struct SamplePlayer {
+    layer: usize,
+    position: f32,
+    step: f32,
+    gain: f32,
+    playing: bool,
+}
+
+impl SamplePlayer {
+    pub fn new() -> Self {
+        Self {
+            layer: 0,
+            position: 0.0,
+            step: 1.0,
+            gain: 0.0,
+            playing: false,
+        }
+    }
+
+    /// Picks the velocity layer and starts playing it. `pitch_ratio` is 1.0 to play the sample at
+    /// its original pitch.
+    pub fn start(&mut self, kit: &SampleKit, velocity: f32, pitch_ratio: f32, sample_rate: f32) {
+        let num_layers = kit.layers.len();
+        if num_layers == 0 {
+            self.playing = false;
+            return;
+        }
+
+        self.layer = ((velocity * num_layers as f32) as usize).min(num_layers - 1);
+        self.step = kit.layers[self.layer].sample_rate / sample_rate * pitch_ratio;
+        self.gain = velocity.sqrt();
+        self.position = 0.0;
+        self.playing = true;
+    }
+
+    pub fn process(&mut self, kit: &SampleKit) -> f32 {
+        if !self.playing {
+            return 0.0;
+        }
+
+        // The kit might have been swapped for a smaller one
+        let Some(layer) = kit.layers.get(self.layer) else {
+            self.playing = false;
+            return 0.0;
+        };
+
+        let index = self.position as usize;
+        if index + 1 >= layer.samples.len() {
+            self.playing = false;
+            return 0.0;
+        }
+
+        let fraction = self.position - index as f32;
+        let sample =
+            layer.samples[index] + (layer.samples[index + 1] - layer.samples[index]) * fraction;
+        self.position += self.step;
+        sample * self.gain
+    }
+
+    pub fn reset(&mut self) {
+        self.playing = false;
+        self.position = 0.0;
+    }
+}
+
+/// Plays the sample kit for every strike.
+pub struct SampleStrike {
+    params: Arc<PockyplockyParams>,
+    sample_rate: f32,
+    fundamental: f32,
+    player: SamplePlayer,
+}
+
+impl SampleStrike {
+    pub fn new(params: Arc<PockyplockyParams>) -> Self {
+        Self {
+            params,
+            sample_rate: DEFAULT_SAMPLE_RATE,
+            fundamental: 0.0,
+            player: SamplePlayer::new(),
+        }
+    }
+}
+
+impl ExciterSource for SampleStrike {
+    fn enabled(&self) -> bool {
+        self.params.sample_enabled.value()
+    }
+
+    fn level(&self) -> f32 {
+        self.params.sample_level.value()
+    }
+
+    fn velocity_sensitivity(&self) -> f32 {
+        self.params.sample_velocity_sensitivity.value()
+    }
+
+    fn envelope(&self) -> Option<EnvelopeShape> {
+        let params = &self.params;
+        params.sample_envelope.value().then(|| EnvelopeShape {
+            attack_ms: params.sample_envelope_attack.value(),
+            attack_curve: params.sample_envelope_attack_shape.value(),
+            decay_ms: params.sample_envelope_decay.value(),
+            decay_curve: params.sample_envelope_decay_shape.value(),
+        })
+    }
+
+    fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+    }
+
+    fn reset(&mut self) {
+        self.player.reset();
+    }
+
+    fn start(&mut self, fundamental: f32, _velocity: f32) -> f32 {
+        self.fundamental = fundamental;
+        0.0
+    }
+
+    fn process_block(
+        &mut self,
+        strikes: &[Strike],
+        _param_buffers: &ParamBuffers,
+        output: &mut [f32],
+    ) {
+        // The kit is being replaced when it can't be read, it's skipped for this block then
+        let Ok(kit_guard) = self.params.sample_kit.try_read() else {
+            return;
+        };
+        let Some(kit) = kit_guard.as_ref() else {
+            return;
+        };
+
+        let pitch_ratio = if self.params.sample_pitch_tracking.value() {
+            self.fundamental / util::midi_note_to_freq(self.params.sample_root_note.value() as u8)
+        } else {
+            1.0
+        };
+
+        let mut strikes = strikes.iter().peekable();
+        for (i, sample) in output.iter_mut().enumerate() {
+            if let Some(strike) = strikes.next_if(|strike| strike.position == i) {
+                self.player
+                    .start(kit, strike.velocity, pitch_ratio, self.sample_rate);
+            }
+            *sample = self.player.process(kit);
+        }
+    }
+}
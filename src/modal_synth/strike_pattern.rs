@@ -0,0 +1,112 @@
+use crate::params::StrikePattern;
+
+const MAX_STRIKES: usize = 16;
+
+// A bouncing mallet stops once its strikes get this soft
+const MIN_BOUNCE_VELOCITY: f32 = 0.02;
+
+/// The strikes that make up a single note, as sample offsets from the start of the note and
+/// velocities. Flams, drags and ruffs are one, two and three soft grace notes leading up to the main
+/// strike. A bouncing mallet starts with the main strike and keeps hitting the bar softer and faster
+/// until it comes to rest.
+#[derive(Clone)]
+pub struct StrikeSequence {
+    times: [usize; MAX_STRIKES],
+    velocities: [f32; MAX_STRIKES],
+    len: usize,
+    next: usize,
+    position: usize,
+}
+
+impl StrikeSequence {
+    pub fn new() -> Self {
+        Self {
+            times: [0; MAX_STRIKES],
+            velocities: [0.0; MAX_STRIKES],
+            len: 0,
+            next: 0,
+            position: 0,
+        }
+    }
+
+    /// `spacing` is the time between strikes in samples. Every following gap is `spacing_decay`
+    /// times the previous one, which only applies to the grace notes for drags and ruffs. Grace
+    /// notes and bounces lose `velocity_falloff` of the velocity of the strike before them.
+    pub fn start(
+        &mut self,
+        pattern: StrikePattern,
+        velocity: f32,
+        spacing: f32,
+        spacing_decay: f32,
+        velocity_falloff: f32,
+    ) {
+        self.len = 0;
+        self.next = 0;
+        self.position = 0;
+
+        let grace_notes = match pattern {
+            StrikePattern::Single => 0,
+            StrikePattern::Flam => 1,
+            StrikePattern::Drag => 2,
+            StrikePattern::Ruff => 3,
+            StrikePattern::Bounce => {
+                let mut time = 0.0;
+                let mut gap = spacing;
+                let mut strike_velocity = velocity;
+                while self.len < MAX_STRIKES && strike_velocity >= MIN_BOUNCE_VELOCITY {
+                    self.push(time, strike_velocity);
+                    time += gap.max(1.0);
+                    gap *= spacing_decay;
+                    strike_velocity *= 1.0 - velocity_falloff;
+                }
+                return;
+            }
+        };
+
+        let grace_velocity = velocity * (1.0 - velocity_falloff);
+        let grace_gap = spacing * spacing_decay;
+        let mut time = 0.0;
+        for _ in 0..grace_notes {
+            self.push(time, grace_velocity);
+            time += grace_gap.max(1.0);
+        }
+        // The main strike comes a full spacing after the last grace note
+        if grace_notes > 0 {
+            time += spacing - grace_gap.max(1.0);
+        }
+        self.push(time, velocity);
+    }
+
+    fn push(&mut self, time: f32, velocity: f32) {
+        self.times[self.len] = time.round() as usize;
+        self.velocities[self.len] = velocity;
+        self.len += 1;
+    }
+
+    /// Time from the first to the last strike in samples.
+    pub fn duration(&self) -> usize {
+        if self.len == 0 {
+            0
+        } else {
+            self.times[self.len - 1]
+        }
+    }
+
+    /// Advances by one sample, returns the velocity of the strike that happens on it if there is
+    /// one.
+    pub fn next_sample(&mut self) -> Option<f32> {
+        let mut strike = None;
+        while self.next < self.len && self.times[self.next] <= self.position {
+            strike = Some(self.velocities[self.next]);
+            self.next += 1;
+        }
+        self.position += 1;
+        strike
+    }
+
+    pub fn reset(&mut self) {
+        self.len = 0;
+        self.next = 0;
+        self.position = 0;
+    }
+}
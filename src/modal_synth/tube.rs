@@ -0,0 +1,86 @@
+use crate::{
+    constants::DEFAULT_SAMPLE_RATE,
+    modal_synth::{
+        modes::{Mode, NUM_MODES},
+        resonator::{ModalResonator, T60_DECAY_FACTOR},
+    },
+};
+
+// Quality factor of the tube resonances at minimum and maximum damping.
+const MAX_Q: f32 = 300.0;
+const MIN_Q: f32 = 10.0;
+
+/// A tuned tube underneath the bar, as found on marimbas and vibraphones. The tube is closed at
+/// the bottom, so it only resonates at odd multiples of its fundamental.
+pub struct TubeResonator {
+    sample_rate: f32,
+    resonator: ModalResonator,
+}
+
+impl TubeResonator {
+    pub fn new() -> Self {
+        Self {
+            sample_rate: DEFAULT_SAMPLE_RATE,
+            resonator: ModalResonator::new(),
+        }
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+        self.resonator.set_sample_rate(sample_rate);
+    }
+
+    pub fn reset(&mut self) {
+        self.resonator.reset();
+    }
+
+    /// Tunes the tube to `frequency`, detuned by `detune` cents. `damping` goes from 0.0 (ringing)
+    /// to 1.0 (heavily damped). Returns the decay time of the tube's fundamental.
+    pub fn start(&mut self, frequency: f32, detune: f32, damping: f32) -> f32 {
+        self.resonator.reset();
+
+        let tube_frequency = frequency * 2.0f32.powf(detune / 1200.0);
+        let q = MAX_Q * (MIN_Q / MAX_Q).powf(damping);
+
+        let modes: [Mode; NUM_MODES] = std::array::from_fn(|i| {
+            let harmonic = (2 * i + 1) as f32;
+            let mode_frequency = tube_frequency * harmonic;
+            // T60 = -ln(1000) * Q / (pi * f)
+            let decay = -T60_DECAY_FACTOR * q / (std::f32::consts::PI * mode_frequency);
+
+            // Normalize to roughly unity gain at resonance, so the level doesn't depend on the
+            // damping or the sample rate
+            let resonance_gain =
+                self.sample_rate * decay / (-T60_DECAY_FACTOR * 4.0 * std::f32::consts::PI);
+
+            if mode_frequency > 20000.0 {
+                Mode {
+                    frequency: 20000.0,
+                    amplitude: 0.0,
+                    decay: 1.0,
+                    aftersound_decay: 0.0,
+                    aftersound_level: 0.0,
+                    beat_frequency: 0.0,
+                    beat_depth: 0.0,
+                }
+            } else {
+                Mode {
+                    frequency: mode_frequency,
+                    amplitude: 1.0 / (harmonic * resonance_gain),
+                    decay,
+                    aftersound_decay: 0.0,
+                    aftersound_level: 0.0,
+                    beat_frequency: 0.0,
+                    beat_depth: 0.0,
+                }
+            }
+        });
+
+        self.resonator.set_modes(&modes);
+        modes[0].decay
+    }
+
+    pub fn process(&mut self, input: f32) -> f32 {
+        self.resonator.process(input)
+    }
+}
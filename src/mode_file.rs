@@ -0,0 +1,152 @@
+use nih_plug::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{modal_synth::modes::NUM_MODES, params::PockyplockyParams};
+
+#[derive(Serialize, Deserialize)]
+pub struct ModeFileEntry {
+    pub ratio: f32,
+    pub amplitude: f32,
+    pub decay_factor: f32,
+    // Relative to the decay of the mode, like in the timbres. Projects saved before modes could
+    // have an aftersound don't have these.
+    #[serde(default)]
+    pub aftersound_decay_factor: f32,
+    #[serde(default)]
+    pub aftersound_level: f32,
+}
+
+/// Modes measured or designed by the user. A mode file is a text file in `~/.pockyplocky/modes`
+/// with a mode on every line: its frequency ratio to the fundamental, its amplitude and optionally
+/// its decay factor. Modes that decay in two stages add the decay factor of the aftersound,
+/// relative to the decay of the mode, and the level of the aftersound. Empty lines and lines
+/// starting with `#` are skipped. Projects know their mode file by its name.
+#[derive(Serialize, Deserialize)]
+pub struct ModeFile {
+    pub name: String,
+    pub modes: Vec<ModeFileEntry>,
+}
+
+pub enum ModeFileTask {
+    // Index of the mode file in alphabetical order
+    Load(usize),
+}
+
+fn modes_directory() -> Option<PathBuf> {
+    let home = std::env::var_os("HOME").or_else(|| std::env::var_os("USERPROFILE"))?;
+    Some(Path::new(&home).join(".pockyplocky").join("modes"))
+}
+
+fn parse_mode(line: &str) -> Option<ModeFileEntry> {
+    let mut values = line.split_whitespace().map(str::parse::<f32>);
+    let ratio = values.next()?.ok()?;
+    let amplitude = values.next()?.ok()?;
+    let decay_factor = match values.next() {
+        Some(value) => value.ok()?,
+        None => 1.0,
+    };
+    // The aftersound needs both its decay factor and its level
+    let (aftersound_decay_factor, aftersound_level) = match values.next() {
+        Some(value) => {
+            let aftersound_decay_factor = value.ok()?;
+            let aftersound_level = values.next()?.ok()?;
+            if aftersound_decay_factor <= 0.0 || !(0.0..=1.0).contains(&aftersound_level) {
+                return None;
+            }
+            (aftersound_decay_factor, aftersound_level)
+        }
+        None => (0.0, 0.0),
+    };
+
+    if ratio <= 0.0 || decay_factor <= 0.0 || values.next().is_some() {
+        return None;
+    }
+
+    Some(ModeFileEntry {
+        ratio,
+        amplitude,
+        decay_factor,
+        aftersound_decay_factor,
+        aftersound_level,
+    })
+}
+
+fn mode_file_path(index: usize) -> Option<PathBuf> {
+    let mut files: Vec<PathBuf> = fs::read_dir(modes_directory()?)
+        .ok()?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|extension| extension.eq_ignore_ascii_case("txt"))
+        })
+        .collect();
+    files.sort();
+    files.into_iter().nth(index)
+}
+
+fn load_mode_file(file: &Path, name: String) -> Option<ModeFile> {
+    let text = match fs::read_to_string(file) {
+        Ok(text) => text,
+        Err(error) => {
+            nih_warn!("Can't load mode file {}: {}", file.display(), error);
+            return None;
+        }
+    };
+
+    let mut modes = Vec::new();
+    for (line_number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        match parse_mode(line) {
+            Some(mode) => modes.push(mode),
+            None => nih_warn!("Skipping line {} of {}", line_number + 1, file.display()),
+        }
+    }
+    modes.truncate(NUM_MODES);
+
+    if modes.is_empty() {
+        return None;
+    }
+
+    Some(ModeFile { name, modes })
+}
+
+/// Runs on the background thread. When the file can't be found, the modes that were stored with
+/// the project are kept. So are the modes of a file with the same name.
+pub fn run_task(params: &PockyplockyParams, task: ModeFileTask) {
+    match task {
+        ModeFileTask::Load(index) => {
+            let Some(file) = mode_file_path(index) else {
+                return;
+            };
+            let Some(name) = file
+                .file_stem()
+                .map(|name| name.to_string_lossy().into_owned())
+            else {
+                return;
+            };
+            let is_loaded = params.mode_file.read().is_ok_and(|mode_file| {
+                mode_file
+                    .as_ref()
+                    .is_some_and(|mode_file| mode_file.name == name)
+            });
+            if is_loaded {
+                return;
+            }
+
+            if let Some(mode_file) = load_mode_file(&file, name) {
+                nih_log!("Loaded mode file {}", mode_file.name);
+                if let Ok(mut loaded_mode_file) = params.mode_file.write() {
+                    *loaded_mode_file = Some(mode_file);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,124 @@
+use rand::Rng;
+use rand_pcg::Pcg32;
+use std::sync::Arc;
+
+use crate::{
+    constants::DEFAULT_SAMPLE_RATE,
+    params::{OnsetPitchMode, PockyplockyParams},
+};
+
+// Release time of the fast envelope and smoothing time of the slow one, in seconds
+const FAST_RELEASE_TIME: f32 = 0.01;
+const SLOW_TIME: f32 = 0.1;
+
+// How far the fast envelope has to rise above the slow one, at no and at full sensitivity
+const MAX_RATIO: f32 = 6.0;
+const MIN_RATIO: f32 = 1.5;
+
+// Quietest level that can trigger, in dB, at no and at full sensitivity
+const MAX_FLOOR_DB: f32 = -20.0;
+const MIN_FLOOR_DB: f32 = -60.0;
+
+// Onsets at this level or louder get full velocity, the velocity range covers this many dB below it
+const FULL_VELOCITY_DB: f32 = -6.0;
+const VELOCITY_RANGE_DB: f32 = 42.0;
+
+const MIN_VELOCITY: f32 = 0.05;
+
+const NUM_ONSET_NOTES: usize = 4;
+
+/// Finds transients in the input, like drum hits, by comparing a fast envelope that jumps up with
+/// every hit to a slow one that follows the overall level.
+pub struct OnsetDetector {
+    params: Arc<PockyplockyParams>,
+    sample_rate: f32,
+    fast_release: f32,
+    slow_coefficient: f32,
+    fast: f32,
+    slow: f32,
+    holdoff: usize, // Samples until the next onset can be detected
+    next_note: usize,
+    prng: Pcg32,
+}
+
+impl OnsetDetector {
+    pub fn new(params: Arc<PockyplockyParams>) -> Self {
+        let mut detector = Self {
+            params,
+            sample_rate: DEFAULT_SAMPLE_RATE,
+            fast_release: 0.0,
+            slow_coefficient: 0.0,
+            fast: 0.0,
+            slow: 0.0,
+            holdoff: 0,
+            next_note: 0,
+            prng: Pcg32::new(11235, 81321),
+        };
+        detector.set_sample_rate(DEFAULT_SAMPLE_RATE);
+        detector
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+        self.fast_release = (-1.0 / (FAST_RELEASE_TIME * sample_rate)).exp();
+        self.slow_coefficient = 1.0 - (-1.0 / (SLOW_TIME * sample_rate)).exp();
+    }
+
+    pub fn reset(&mut self) {
+        self.fast = 0.0;
+        self.slow = 0.0;
+        self.holdoff = 0;
+        self.next_note = 0;
+    }
+
+    /// Processes one sample of the input, returns the velocity of the onset if one starts here.
+    pub fn process(&mut self, input: f32) -> Option<f32> {
+        self.fast = input.abs().max(self.fast * self.fast_release);
+        // The slow envelope is updated after the check, so the onset itself doesn't raise it
+        let slow = self.slow;
+        self.slow += (self.fast - self.slow) * self.slow_coefficient;
+
+        if self.holdoff > 0 {
+            self.holdoff -= 1;
+            return None;
+        }
+
+        let sensitivity = self.params.onset_sensitivity.value();
+        let ratio = MAX_RATIO + (MIN_RATIO - MAX_RATIO) * sensitivity;
+        let floor_db = MAX_FLOOR_DB + (MIN_FLOOR_DB - MAX_FLOOR_DB) * sensitivity;
+
+        let level_db = 20.0 * self.fast.max(1e-9).log10();
+        if self.fast < slow * ratio || level_db < floor_db {
+            return None;
+        }
+
+        self.holdoff =
+            (self.params.onset_retrigger_time.value() * 0.001 * self.sample_rate) as usize;
+        let velocity = (level_db - FULL_VELOCITY_DB + VELOCITY_RANGE_DB) / VELOCITY_RANGE_DB;
+        Some(velocity.clamp(MIN_VELOCITY, 1.0))
+    }
+
+    /// Picks the note to play for an onset from the onset notes.
+    pub fn select_note(&mut self, velocity: f32) -> u8 {
+        let params = &self.params;
+        let notes: [i32; NUM_ONSET_NOTES] = [
+            params.onset_note_1.value(),
+            params.onset_note_2.value(),
+            params.onset_note_3.value(),
+            params.onset_note_4.value(),
+        ];
+        let count = (params.onset_note_count.value() as usize).clamp(1, NUM_ONSET_NOTES);
+
+        let index = match params.onset_pitch_mode.value() {
+            OnsetPitchMode::Cycle => {
+                let index = self.next_note % count;
+                self.next_note = index + 1;
+                index
+            }
+            OnsetPitchMode::Random => self.prng.gen_range(0..count),
+            // Harder hits play the later notes
+            OnsetPitchMode::Velocity => ((velocity * count as f32) as usize).min(count - 1),
+        };
+        notes[index] as u8
+    }
+}
@@ -1,8 +1,8 @@
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
 
 use nih_plug::prelude::*;
 
-use crate::constants::MAX_BLOCK_SIZE;
+use crate::{constants::MAX_BLOCK_SIZE, mode_file::ModeFile, sample_kit::SampleKit};
 
 #[derive(Params)]
 pub struct PockyplockyParams {
@@ -17,12 +17,54 @@ pub struct PockyplockyParams {
     // Exciter Params
     #[id = "strike"]
     pub strike: BoolParam,
+    #[id = "strike_level"]
+    pub strike_level: FloatParam,
+    #[id = "strike_velocity_sensitivity"]
+    pub strike_velocity_sensitivity: FloatParam,
+    #[id = "strike_envelope"]
+    pub strike_envelope: BoolParam,
+    #[id = "strike_envelope_attack"]
+    pub strike_envelope_attack: FloatParam,
+    #[id = "strike_envelope_attack_shape"]
+    pub strike_envelope_attack_shape: EnumParam<BreathAttackCurve>,
+    #[id = "strike_envelope_decay"]
+    pub strike_envelope_decay: FloatParam,
+    #[id = "strike_envelope_decay_shape"]
+    pub strike_envelope_decay_shape: EnumParam<BreathDecayCurve>,
     #[id = "mallet"]
     pub mallet: BoolParam,
+    #[id = "mallet_level"]
+    pub mallet_level: FloatParam,
+    #[id = "mallet_velocity_sensitivity"]
+    pub mallet_velocity_sensitivity: FloatParam,
+    #[id = "mallet_envelope"]
+    pub mallet_envelope: BoolParam,
+    #[id = "mallet_envelope_attack"]
+    pub mallet_envelope_attack: FloatParam,
+    #[id = "mallet_envelope_attack_shape"]
+    pub mallet_envelope_attack_shape: EnumParam<BreathAttackCurve>,
+    #[id = "mallet_envelope_decay"]
+    pub mallet_envelope_decay: FloatParam,
+    #[id = "mallet_envelope_decay_shape"]
+    pub mallet_envelope_decay_shape: EnumParam<BreathDecayCurve>,
     #[id = "mallet_hardness"]
     pub mallet_hardness: FloatParam,
+    #[id = "mallet_model"]
+    pub mallet_model: EnumParam<MalletModel>,
+    #[id = "mallet_material"]
+    pub mallet_material: EnumParam<MalletMaterial>,
+    #[id = "strike_pattern"]
+    pub strike_pattern: EnumParam<StrikePattern>,
+    #[id = "strike_spacing"]
+    pub strike_spacing: FloatParam,
+    #[id = "strike_spacing_decay"]
+    pub strike_spacing_decay: FloatParam,
+    #[id = "strike_velocity_falloff"]
+    pub strike_velocity_falloff: FloatParam,
     #[id = "breath_level"]
     pub breath_level: FloatParam,
+    #[id = "breath_velocity_sensitivity"]
+    pub breath_velocity_sensitivity: FloatParam,
     #[id = "breath_attack"]
     pub breath_attack: FloatParam,
     #[id = "breath_attack_shape"]
@@ -31,12 +73,276 @@ pub struct PockyplockyParams {
     pub breath_decay: FloatParam,
     #[id = "breath_decay_shape"]
     pub breath_decay_shape: EnumParam<BreathDecayCurve>,
+    #[id = "breath_mode"]
+    pub breath_mode: EnumParam<BreathMode>,
+    #[id = "breath_source"]
+    pub breath_source: EnumParam<BreathSource>,
+    #[id = "breath_colour"]
+    pub breath_colour: EnumParam<NoiseColour>,
+    #[id = "breath_filter_enabled"]
+    pub breath_filter_enabled: BoolParam,
+    #[id = "breath_filter_q"]
+    pub breath_filter_q: FloatParam,
+    #[id = "breath_filter_offset"]
+    pub breath_filter_offset: FloatParam,
+
+    // Sample exciter
+    #[id = "sample_enabled"]
+    pub sample_enabled: BoolParam,
+    #[id = "sample_kit_index"]
+    pub sample_kit_index: IntParam,
+    #[id = "sample_level"]
+    pub sample_level: FloatParam,
+    #[id = "sample_velocity_sensitivity"]
+    pub sample_velocity_sensitivity: FloatParam,
+    #[id = "sample_envelope"]
+    pub sample_envelope: BoolParam,
+    #[id = "sample_envelope_attack"]
+    pub sample_envelope_attack: FloatParam,
+    #[id = "sample_envelope_attack_shape"]
+    pub sample_envelope_attack_shape: EnumParam<BreathAttackCurve>,
+    #[id = "sample_envelope_decay"]
+    pub sample_envelope_decay: FloatParam,
+    #[id = "sample_envelope_decay_shape"]
+    pub sample_envelope_decay_shape: EnumParam<BreathDecayCurve>,
+    #[id = "sample_pitch_tracking"]
+    pub sample_pitch_tracking: BoolParam,
+    #[id = "sample_root_note"]
+    pub sample_root_note: IntParam,
+    #[persist = "sample_kit"]
+    pub sample_kit: RwLock<Option<SampleKit>>,
+
+    // Roll
+    #[id = "roll_enabled"]
+    pub roll_enabled: BoolParam,
+    #[id = "roll_rate"]
+    pub roll_rate: FloatParam,
+    #[id = "roll_sync"]
+    pub roll_sync: BoolParam,
+    #[id = "roll_division"]
+    pub roll_division: EnumParam<RollDivision>,
+    #[id = "roll_accent"]
+    pub roll_accent: FloatParam,
+    #[id = "roll_humanize"]
+    pub roll_humanize: FloatParam,
+
+    // Bow
+    #[id = "bow_enabled"]
+    pub bow_enabled: BoolParam,
+    #[id = "bow_pressure"]
+    pub bow_pressure: FloatParam,
+    #[id = "bow_velocity"]
+    pub bow_velocity: FloatParam,
+    #[id = "bow_position"]
+    pub bow_position: FloatParam,
+
+    // Jet
+    #[id = "jet_enabled"]
+    pub jet_enabled: BoolParam,
+    #[id = "jet_pressure"]
+    pub jet_pressure: FloatParam,
+    #[id = "jet_ratio"]
+    pub jet_ratio: FloatParam,
+    #[id = "jet_noise"]
+    pub jet_noise: FloatParam,
+
+    // Feedback
+    #[id = "feedback_enabled"]
+    pub feedback_enabled: BoolParam,
+    #[id = "feedback_amount"]
+    pub feedback_amount: FloatParam,
+    #[id = "feedback_delay"]
+    pub feedback_delay: FloatParam,
+    #[id = "feedback_invert"]
+    pub feedback_invert: BoolParam,
+
+    // Particles
+    #[id = "particles_enabled"]
+    pub particles_enabled: BoolParam,
+    #[id = "particle_count"]
+    pub particle_count: FloatParam,
+    #[id = "particle_energy"]
+    pub particle_energy: FloatParam,
+    #[id = "particle_level"]
+    pub particle_level: FloatParam,
+    #[id = "particle_velocity_sensitivity"]
+    pub particle_velocity_sensitivity: FloatParam,
+    #[id = "particle_envelope"]
+    pub particle_envelope: BoolParam,
+    #[id = "particle_envelope_attack"]
+    pub particle_envelope_attack: FloatParam,
+    #[id = "particle_envelope_attack_shape"]
+    pub particle_envelope_attack_shape: EnumParam<BreathAttackCurve>,
+    #[id = "particle_envelope_decay"]
+    pub particle_envelope_decay: FloatParam,
+    #[id = "particle_envelope_decay_shape"]
+    pub particle_envelope_decay_shape: EnumParam<BreathDecayCurve>,
+    #[id = "particle_decay"]
+    pub particle_decay: FloatParam,
+    #[id = "particle_gesture"]
+    pub particle_gesture: EnumParam<ParticleGesture>,
+    #[id = "scrape_rate"]
+    pub scrape_rate: FloatParam,
+    #[id = "shake_cc"]
+    pub shake_cc: IntParam,
+
+    // Keyswitches
+    #[id = "keyswitches_enabled"]
+    pub keyswitches_enabled: BoolParam,
+    #[id = "keyswitch_base_note"]
+    pub keyswitch_base_note: IntParam,
+
+    // Where the modes come from
+    #[id = "mode_source"]
+    pub mode_source: EnumParam<ModeSourceKind>,
+    #[id = "morph_timbre"]
+    pub morph_timbre: EnumParam<Timbre>,
+    #[id = "morph_amount"]
+    pub morph_amount: FloatParam,
+    #[id = "mode_file_index"]
+    pub mode_file_index: IntParam,
+    #[persist = "mode_file"]
+    pub mode_file: RwLock<Option<ModeFile>>,
+    #[id = "geometry"]
+    pub geometry: EnumParam<Geometry>,
+    #[id = "plate_aspect"]
+    pub plate_aspect: FloatParam,
 
     // Tweaking the modes
     #[id = "fundamental_balance"]
     pub fundamental_balance: FloatParam,
     #[id = "sparkle"]
     pub sparkle: FloatParam,
+    #[id = "beating_rate"]
+    pub beating_rate: FloatParam,
+    #[id = "beating_depth"]
+    pub beating_depth: FloatParam,
+    #[id = "shimmer"]
+    pub shimmer: FloatParam,
+    #[id = "bloom_time"]
+    pub bloom_time: FloatParam,
+
+    // Pitch
+    #[id = "tension"]
+    pub tension: FloatParam,
+    #[id = "pitch_envelope_amount"]
+    pub pitch_envelope_amount: FloatParam,
+    #[id = "pitch_envelope_time"]
+    pub pitch_envelope_time: FloatParam,
+
+    // Mod envelope
+    #[id = "mod_envelope_enabled"]
+    pub mod_envelope_enabled: BoolParam,
+    #[id = "mod_envelope_delay"]
+    pub mod_envelope_delay: FloatParam,
+    #[id = "mod_envelope_attack"]
+    pub mod_envelope_attack: FloatParam,
+    #[id = "mod_envelope_attack_tension"]
+    pub mod_envelope_attack_tension: FloatParam,
+    #[id = "mod_envelope_hold"]
+    pub mod_envelope_hold: FloatParam,
+    #[id = "mod_envelope_decay"]
+    pub mod_envelope_decay: FloatParam,
+    #[id = "mod_envelope_decay_tension"]
+    pub mod_envelope_decay_tension: FloatParam,
+    #[id = "mod_envelope_sustain"]
+    pub mod_envelope_sustain: FloatParam,
+    #[id = "mod_envelope_release"]
+    pub mod_envelope_release: FloatParam,
+    #[id = "mod_envelope_release_tension"]
+    pub mod_envelope_release_tension: FloatParam,
+    #[id = "mod_envelope_legato"]
+    pub mod_envelope_legato: BoolParam,
+    #[id = "mod_envelope_pitch"]
+    pub mod_envelope_pitch: FloatParam,
+    #[id = "mod_envelope_fold"]
+    pub mod_envelope_fold: FloatParam,
+
+    // Bell
+    #[id = "bell_hum_tuning"]
+    pub bell_hum_tuning: FloatParam,
+    #[id = "bell_hum_level"]
+    pub bell_hum_level: FloatParam,
+    #[id = "bell_prime_tuning"]
+    pub bell_prime_tuning: FloatParam,
+    #[id = "bell_prime_level"]
+    pub bell_prime_level: FloatParam,
+    #[id = "bell_tierce_tuning"]
+    pub bell_tierce_tuning: FloatParam,
+    #[id = "bell_tierce_level"]
+    pub bell_tierce_level: FloatParam,
+    #[id = "bell_quint_tuning"]
+    pub bell_quint_tuning: FloatParam,
+    #[id = "bell_quint_level"]
+    pub bell_quint_level: FloatParam,
+    #[id = "bell_nominal_tuning"]
+    pub bell_nominal_tuning: FloatParam,
+    #[id = "bell_nominal_level"]
+    pub bell_nominal_level: FloatParam,
+    #[id = "bell_upper_tuning"]
+    pub bell_upper_tuning: FloatParam,
+    #[id = "bell_upper_level"]
+    pub bell_upper_level: FloatParam,
+    #[id = "bell_warble"]
+    pub bell_warble: FloatParam,
+    #[id = "bell_third"]
+    pub bell_third: EnumParam<BellThird>,
+
+    // Material
+    #[id = "material_enabled"]
+    pub material_enabled: BoolParam,
+    #[id = "material"]
+    pub material: FloatParam,
+    #[id = "size"]
+    pub size: FloatParam,
+
+    // Tube resonator
+    #[id = "tube_enabled"]
+    pub tube_enabled: BoolParam,
+    #[id = "tube_level"]
+    pub tube_level: FloatParam,
+    #[id = "tube_detune"]
+    pub tube_detune: FloatParam,
+    #[id = "tube_damping"]
+    pub tube_damping: FloatParam,
+
+    // Buzz
+    #[id = "buzz_enabled"]
+    pub buzz_enabled: BoolParam,
+    #[id = "buzz_threshold"]
+    pub buzz_threshold: FloatParam,
+    #[id = "buzz_level"]
+    pub buzz_level: FloatParam,
+    #[id = "buzz_colour"]
+    pub buzz_colour: FloatParam,
+
+    // Audio input
+    #[id = "audio_input_enabled"]
+    pub audio_input_enabled: BoolParam,
+    #[id = "input_gain"]
+    pub input_gain: FloatParam,
+    #[id = "dry_wet"]
+    pub dry_wet: FloatParam,
+
+    // Onset trigger
+    #[id = "onset_enabled"]
+    pub onset_enabled: BoolParam,
+    #[id = "onset_sensitivity"]
+    pub onset_sensitivity: FloatParam,
+    #[id = "onset_retrigger_time"]
+    pub onset_retrigger_time: FloatParam,
+    #[id = "onset_pitch_mode"]
+    pub onset_pitch_mode: EnumParam<OnsetPitchMode>,
+    #[id = "onset_note_count"]
+    pub onset_note_count: IntParam,
+    #[id = "onset_note_1"]
+    pub onset_note_1: IntParam,
+    #[id = "onset_note_2"]
+    pub onset_note_2: IntParam,
+    #[id = "onset_note_3"]
+    pub onset_note_3: IntParam,
+    #[id = "onset_note_4"]
+    pub onset_note_4: IntParam,
 
     // Effects
 
@@ -46,6 +352,18 @@ pub struct PockyplockyParams {
     #[id = "wave_folder_amount"]
     pub wave_folder_amount: FloatParam,
 
+    // Click
+    #[id = "click_level"]
+    pub click_level: FloatParam,
+    #[id = "click_tone"]
+    pub click_tone: FloatParam,
+
+    // Transient shaper
+    #[id = "transient_attack"]
+    pub transient_attack: FloatParam,
+    #[id = "transient_sustain"]
+    pub transient_sustain: FloatParam,
+
     // Second voice
     #[id = "second_voice_enabled"]
     pub second_voice_enabled: BoolParam,
@@ -75,6 +393,129 @@ pub enum Timbre {
     MetalCup,
     #[name = "Cowbell"]
     Cowbell,
+    #[name = "Gong"]
+    Gong,
+    #[name = "Bell"]
+    Bell,
+}
+
+// The order matches the sources of the `ModeCalculator`
+#[derive(Enum, Debug, Clone, Copy, PartialEq)]
+pub enum ModeSourceKind {
+    #[name = "Timbre"]
+    Timbre,
+    #[name = "Morph"]
+    Morph,
+    #[name = "User File"]
+    UserFile,
+    #[name = "Geometry"]
+    Geometry,
+}
+
+#[derive(Enum, Debug, Clone, Copy, PartialEq)]
+pub enum Geometry {
+    #[name = "Free Bar"]
+    FreeBar,
+    #[name = "Clamped Bar"]
+    ClampedBar,
+    #[name = "Membrane"]
+    Membrane,
+    #[name = "Clamped Plate"]
+    ClampedPlate,
+    #[name = "Rectangular Plate"]
+    RectangularPlate,
+}
+
+#[derive(Enum, Debug, Clone, Copy, PartialEq)]
+pub enum BellThird {
+    #[name = "Minor Third"]
+    Minor,
+    #[name = "Major Third"]
+    Major,
+}
+
+#[derive(Enum, Debug, Clone, Copy, PartialEq)]
+pub enum MalletModel {
+    #[name = "Classic"]
+    Classic,
+    #[name = "Physical"]
+    Physical,
+}
+
+#[derive(Enum, Debug, Clone, Copy, PartialEq)]
+pub enum MalletMaterial {
+    #[name = "Yarn"]
+    Yarn,
+    #[name = "Rubber"]
+    Rubber,
+    #[name = "Plastic"]
+    Plastic,
+    #[name = "Rosewood"]
+    Rosewood,
+    #[name = "Brass"]
+    Brass,
+    #[name = "Felt"]
+    Felt,
+    #[name = "Superball"]
+    Superball,
+}
+
+#[derive(Enum, Debug, Clone, Copy, PartialEq)]
+pub enum StrikePattern {
+    #[name = "Single"]
+    Single,
+    #[name = "Flam"]
+    Flam,
+    #[name = "Drag"]
+    Drag,
+    #[name = "Ruff"]
+    Ruff,
+    #[name = "Bounce"]
+    Bounce,
+}
+
+#[derive(Enum, Debug, Clone, Copy, PartialEq)]
+pub enum RollDivision {
+    #[name = "1/8"]
+    Eighth,
+    #[name = "1/8T"]
+    EighthTriplet,
+    #[name = "1/16"]
+    Sixteenth,
+    #[name = "1/16T"]
+    SixteenthTriplet,
+    #[name = "1/32"]
+    ThirtySecond,
+}
+
+impl RollDivision {
+    pub fn strikes_per_beat(self) -> f32 {
+        match self {
+            RollDivision::Eighth => 2.0,
+            RollDivision::EighthTriplet => 3.0,
+            RollDivision::Sixteenth => 4.0,
+            RollDivision::SixteenthTriplet => 6.0,
+            RollDivision::ThirtySecond => 8.0,
+        }
+    }
+}
+
+#[derive(Enum, Debug, Clone, Copy, PartialEq)]
+pub enum ParticleGesture {
+    #[name = "Shake"]
+    Shake,
+    #[name = "Scrape"]
+    Scrape,
+}
+
+#[derive(Enum, Debug, Clone, Copy, PartialEq)]
+pub enum OnsetPitchMode {
+    #[name = "Cycle"]
+    Cycle,
+    #[name = "Random"]
+    Random,
+    #[name = "Velocity"]
+    Velocity,
 }
 
 #[derive(Enum, Debug, Clone, Copy, PartialEq)]
@@ -87,13 +528,96 @@ pub enum BreathAttackCurve {
     Exponential,
 }
 
-// Logarithmic decay doesn't seem to be working properly in the envelope currently, disable it for now.
+#[derive(Enum, Debug, Clone, Copy, PartialEq)]
+pub enum BreathMode {
+    #[name = "One Shot"]
+    OneShot,
+    #[name = "Sustained"]
+    Sustained,
+}
+
+#[derive(Enum, Debug, Clone, Copy, PartialEq)]
+pub enum BreathSource {
+    #[name = "Breath Controller"]
+    BreathController,
+    #[name = "Channel Aftertouch"]
+    ChannelPressure,
+    #[name = "Poly Pressure"]
+    PolyPressure,
+}
+
+#[derive(Enum, Debug, Clone, Copy, PartialEq)]
+pub enum NoiseColour {
+    #[name = "White"]
+    White,
+    #[name = "Pink"]
+    Pink,
+    #[name = "Brown"]
+    Brown,
+}
+
 #[derive(Enum, Debug, Clone, Copy, PartialEq)]
 pub enum BreathDecayCurve {
     #[name = "Linear"]
     Linear,
     #[name = "Exponential"]
     Exponential,
+    #[name = "Logarithmic"]
+    Logarithmic,
+}
+
+fn bell_tuning_param(name: &str) -> FloatParam {
+    FloatParam::new(
+        name,
+        0.0,
+        FloatRange::Linear {
+            min: -100.0,
+            max: 100.0,
+        },
+    )
+    .with_unit(" cents")
+}
+
+fn bell_level_param(name: &str) -> FloatParam {
+    FloatParam::new(name, 1.0, FloatRange::Linear { min: 0.0, max: 2.0 })
+}
+
+fn onset_note_param(name: &str, default: i32) -> IntParam {
+    IntParam::new(name, default, IntRange::Linear { min: 0, max: 127 })
+        .with_value_to_string(formatters::v2s_i32_note_formatter())
+        .with_string_to_value(formatters::s2v_i32_note_formatter())
+}
+
+fn layer_level_param(name: &str) -> FloatParam {
+    FloatParam::new(name, 1.0, FloatRange::Linear { min: 0.0, max: 2.0 })
+}
+
+fn velocity_sensitivity_param(name: &str) -> FloatParam {
+    FloatParam::new(name, 1.0, FloatRange::Linear { min: 0.0, max: 1.0 })
+}
+
+fn envelope_time_param(name: &str, default: f32, max: f32) -> FloatParam {
+    FloatParam::new(
+        name,
+        default,
+        FloatRange::Skewed {
+            min: 0.0,
+            max,
+            factor: FloatRange::skew_factor(-2.0),
+        },
+    )
+    .with_unit(" ms")
+}
+
+fn envelope_tension_param(name: &str, default: f32) -> FloatParam {
+    FloatParam::new(
+        name,
+        default,
+        FloatRange::Linear {
+            min: -1.0,
+            max: 1.0,
+        },
+    )
 }
 
 impl Default for PockyplockyParams {
@@ -118,8 +642,34 @@ impl Default for PockyplockyParams {
             timbre: EnumParam::new("Timbre", Timbre::Xylophone),
 
             strike: BoolParam::new("Strike", false),
+            strike_level: layer_level_param("Strike Level"),
+            strike_velocity_sensitivity: velocity_sensitivity_param("Strike Velocity Sensitivity"),
+            strike_envelope: BoolParam::new("Strike Envelope", false),
+            strike_envelope_attack: envelope_time_param("Strike Envelope Attack", 0.0, 500.0),
+            strike_envelope_attack_shape: EnumParam::new(
+                "Strike Envelope Attack Shape",
+                BreathAttackCurve::Linear,
+            ),
+            strike_envelope_decay: envelope_time_param("Strike Envelope Decay", 200.0, 2000.0),
+            strike_envelope_decay_shape: EnumParam::new(
+                "Strike Envelope Decay Shape",
+                BreathDecayCurve::Exponential,
+            ),
 
             mallet: BoolParam::new("Mallet", true),
+            mallet_level: layer_level_param("Mallet Level"),
+            mallet_velocity_sensitivity: velocity_sensitivity_param("Mallet Velocity Sensitivity"),
+            mallet_envelope: BoolParam::new("Mallet Envelope", false),
+            mallet_envelope_attack: envelope_time_param("Mallet Envelope Attack", 0.0, 500.0),
+            mallet_envelope_attack_shape: EnumParam::new(
+                "Mallet Envelope Attack Shape",
+                BreathAttackCurve::Linear,
+            ),
+            mallet_envelope_decay: envelope_time_param("Mallet Envelope Decay", 200.0, 2000.0),
+            mallet_envelope_decay_shape: EnumParam::new(
+                "Mallet Envelope Decay Shape",
+                BreathDecayCurve::Exponential,
+            ),
 
             mallet_hardness: FloatParam::new(
                 "Mallet Hardness",
@@ -127,6 +677,35 @@ impl Default for PockyplockyParams {
                 FloatRange::Linear { min: 0.0, max: 1.0 },
             ),
 
+            mallet_model: EnumParam::new("Mallet Model", MalletModel::Classic),
+
+            mallet_material: EnumParam::new("Mallet Material", MalletMaterial::Rubber),
+
+            strike_pattern: EnumParam::new("Strike Pattern", StrikePattern::Single),
+
+            strike_spacing: FloatParam::new(
+                "Strike Spacing",
+                40.0,
+                FloatRange::Skewed {
+                    min: 5.0,
+                    max: 500.0,
+                    factor: FloatRange::skew_factor(-1.0),
+                },
+            )
+            .with_unit(" ms"),
+
+            strike_spacing_decay: FloatParam::new(
+                "Strike Spacing Decay",
+                0.75,
+                FloatRange::Linear { min: 0.2, max: 1.0 },
+            ),
+
+            strike_velocity_falloff: FloatParam::new(
+                "Strike Velocity Falloff",
+                0.5,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            ),
+
             breath_level: FloatParam::new(
                 "Breath Level",
                 0.0,
@@ -134,6 +713,8 @@ impl Default for PockyplockyParams {
             )
             .with_smoother(SmoothingStyle::Linear(50.0)),
 
+            breath_velocity_sensitivity: velocity_sensitivity_param("Breath Velocity Sensitivity"),
+
             breath_attack: FloatParam::new(
                 "Breath Attack",
                 10.0,
@@ -158,6 +739,246 @@ impl Default for PockyplockyParams {
 
             breath_decay_shape: EnumParam::new("Breath Decay Shape", BreathDecayCurve::Exponential),
 
+            breath_mode: EnumParam::new("Breath Mode", BreathMode::OneShot),
+
+            breath_source: EnumParam::new("Breath Source", BreathSource::BreathController),
+
+            breath_colour: EnumParam::new("Breath Colour", NoiseColour::White),
+
+            breath_filter_enabled: BoolParam::new("Breath Filter", false),
+
+            breath_filter_q: FloatParam::new(
+                "Breath Filter Q",
+                2.0,
+                FloatRange::Skewed {
+                    min: 0.5,
+                    max: 20.0,
+                    factor: FloatRange::skew_factor(-1.5),
+                },
+            ),
+
+            breath_filter_offset: FloatParam::new(
+                "Breath Filter Offset",
+                0.0,
+                FloatRange::Linear {
+                    min: -24.0,
+                    max: 24.0,
+                },
+            )
+            .with_unit(" st"),
+
+            sample_enabled: BoolParam::new("Sample", false),
+
+            sample_kit_index: IntParam::new("Sample Kit", 0, IntRange::Linear { min: 0, max: 63 }),
+
+            sample_level: FloatParam::new(
+                "Sample Level",
+                0.2,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            ),
+
+            sample_velocity_sensitivity: velocity_sensitivity_param("Sample Velocity Sensitivity"),
+            sample_envelope: BoolParam::new("Sample Envelope", false),
+            sample_envelope_attack: envelope_time_param("Sample Envelope Attack", 0.0, 500.0),
+            sample_envelope_attack_shape: EnumParam::new(
+                "Sample Envelope Attack Shape",
+                BreathAttackCurve::Linear,
+            ),
+            sample_envelope_decay: envelope_time_param("Sample Envelope Decay", 200.0, 2000.0),
+            sample_envelope_decay_shape: EnumParam::new(
+                "Sample Envelope Decay Shape",
+                BreathDecayCurve::Exponential,
+            ),
+
+            sample_pitch_tracking: BoolParam::new("Sample Pitch Tracking", false),
+
+            sample_root_note: IntParam::new(
+                "Sample Root Note",
+                60,
+                IntRange::Linear { min: 0, max: 127 },
+            )
+            .with_value_to_string(formatters::v2s_i32_note_formatter())
+            .with_string_to_value(formatters::s2v_i32_note_formatter()),
+
+            sample_kit: RwLock::new(None),
+
+            roll_enabled: BoolParam::new("Roll", false),
+
+            roll_rate: FloatParam::new(
+                "Roll Rate",
+                12.0,
+                FloatRange::Linear {
+                    min: 2.0,
+                    max: 30.0,
+                },
+            )
+            .with_unit(" Hz"),
+
+            roll_sync: BoolParam::new("Roll Tempo Sync", false),
+
+            roll_division: EnumParam::new("Roll Division", RollDivision::Sixteenth),
+
+            roll_accent: FloatParam::new(
+                "Roll Accent",
+                0.2,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            ),
+
+            roll_humanize: FloatParam::new(
+                "Roll Humanize",
+                0.2,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            ),
+
+            bow_enabled: BoolParam::new("Bow", false),
+
+            bow_pressure: FloatParam::new(
+                "Bow Pressure",
+                0.5,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            ),
+
+            bow_velocity: FloatParam::new(
+                "Bow Velocity",
+                0.5,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            ),
+
+            bow_position: FloatParam::new(
+                "Bow Position",
+                0.13,
+                FloatRange::Linear {
+                    min: 0.02,
+                    max: 0.5,
+                },
+            ),
+
+            jet_enabled: BoolParam::new("Jet", false),
+
+            jet_pressure: FloatParam::new(
+                "Jet Pressure",
+                0.8,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            ),
+
+            jet_ratio: FloatParam::new(
+                "Jet Ratio",
+                0.32,
+                FloatRange::Linear {
+                    min: 0.05,
+                    max: 1.0,
+                },
+            ),
+
+            jet_noise: FloatParam::new("Jet Noise", 0.1, FloatRange::Linear { min: 0.0, max: 1.0 }),
+
+            feedback_enabled: BoolParam::new("Feedback", false),
+
+            feedback_amount: FloatParam::new(
+                "Feedback Amount",
+                0.4,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            ),
+
+            feedback_delay: FloatParam::new(
+                "Feedback Delay",
+                0.0,
+                FloatRange::Linear {
+                    min: 0.0,
+                    max: 50.0,
+                },
+            )
+            .with_unit(" ms"),
+
+            feedback_invert: BoolParam::new("Feedback Invert", false),
+
+            particles_enabled: BoolParam::new("Particles", false),
+
+            particle_count: FloatParam::new(
+                "Particle Count",
+                64.0,
+                FloatRange::Skewed {
+                    min: 1.0,
+                    max: 1024.0,
+                    factor: FloatRange::skew_factor(-2.0),
+                },
+            )
+            .with_step_size(1.0),
+
+            particle_energy: FloatParam::new(
+                "Particle Energy",
+                0.5,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            ),
+
+            particle_level: layer_level_param("Particle Level"),
+
+            particle_velocity_sensitivity: velocity_sensitivity_param(
+                "Particle Velocity Sensitivity",
+            ),
+            particle_envelope: BoolParam::new("Particle Envelope", false),
+            particle_envelope_attack: envelope_time_param("Particle Envelope Attack", 0.0, 500.0),
+            particle_envelope_attack_shape: EnumParam::new(
+                "Particle Envelope Attack Shape",
+                BreathAttackCurve::Linear,
+            ),
+            particle_envelope_decay: envelope_time_param("Particle Envelope Decay", 200.0, 2000.0),
+            particle_envelope_decay_shape: EnumParam::new(
+                "Particle Envelope Decay Shape",
+                BreathDecayCurve::Exponential,
+            ),
+
+            particle_decay: FloatParam::new(
+                "Particle Decay",
+                300.0,
+                FloatRange::Skewed {
+                    min: 20.0,
+                    max: 5000.0,
+                    factor: FloatRange::skew_factor(-1.0),
+                },
+            )
+            .with_unit(" ms"),
+
+            particle_gesture: EnumParam::new("Particle Gesture", ParticleGesture::Shake),
+
+            scrape_rate: FloatParam::new(
+                "Scrape Rate",
+                12.0,
+                FloatRange::Linear {
+                    min: 1.0,
+                    max: 40.0,
+                },
+            )
+            .with_unit(" Hz"),
+
+            shake_cc: IntParam::new("Shake CC", 20, IntRange::Linear { min: 0, max: 127 }),
+
+            keyswitches_enabled: BoolParam::new("Keyswitches", false),
+
+            keyswitch_base_note: IntParam::new(
+                "Keyswitch Base Note",
+                24,
+                IntRange::Linear { min: 0, max: 122 },
+            )
+            .with_value_to_string(formatters::v2s_i32_note_formatter())
+            .with_string_to_value(formatters::s2v_i32_note_formatter()),
+
+            mode_source: EnumParam::new("Mode Source", ModeSourceKind::Timbre),
+            morph_timbre: EnumParam::new("Morph Timbre", Timbre::Gong),
+            morph_amount: FloatParam::new(
+                "Morph Amount",
+                0.5,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            ),
+            mode_file_index: IntParam::new("Mode File", 0, IntRange::Linear { min: 0, max: 63 }),
+            mode_file: RwLock::new(None),
+            geometry: EnumParam::new("Geometry", Geometry::FreeBar),
+            plate_aspect: FloatParam::new(
+                "Plate Aspect Ratio",
+                1.5,
+                FloatRange::Linear { min: 1.0, max: 4.0 },
+            ),
+
             fundamental_balance: FloatParam::new(
                 "Fundamental Balance",
                 0.0,
@@ -176,6 +997,218 @@ impl Default for PockyplockyParams {
                 },
             ),
 
+            beating_rate: FloatParam::new(
+                "Beating Rate",
+                1.0,
+                FloatRange::Linear {
+                    min: 0.0,
+                    max: 10.0,
+                },
+            )
+            .with_unit(" Hz"),
+
+            beating_depth: FloatParam::new(
+                "Beating Depth",
+                0.0,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            ),
+
+            shimmer: FloatParam::new("Shimmer", 0.0, FloatRange::Linear { min: 0.0, max: 1.0 }),
+
+            bloom_time: FloatParam::new(
+                "Bloom Time",
+                500.0,
+                FloatRange::Linear {
+                    min: 10.0,
+                    max: 3000.0,
+                },
+            )
+            .with_unit(" ms"),
+
+            tension: FloatParam::new("Tension", 0.0, FloatRange::Linear { min: 0.0, max: 1.0 }),
+
+            pitch_envelope_amount: FloatParam::new(
+                "Pitch Envelope Amount",
+                0.0,
+                FloatRange::Linear {
+                    min: -24.0,
+                    max: 24.0,
+                },
+            )
+            .with_unit(" st"),
+
+            pitch_envelope_time: FloatParam::new(
+                "Pitch Envelope Time",
+                50.0,
+                FloatRange::Linear {
+                    min: 1.0,
+                    max: 1000.0,
+                },
+            )
+            .with_unit(" ms"),
+
+            mod_envelope_enabled: BoolParam::new("Mod Envelope", false),
+            mod_envelope_delay: envelope_time_param("Mod Envelope Delay", 0.0, 2000.0),
+            mod_envelope_attack: envelope_time_param("Mod Envelope Attack", 10.0, 5000.0),
+            mod_envelope_attack_tension: envelope_tension_param("Mod Envelope Attack Tension", 0.0),
+            mod_envelope_hold: envelope_time_param("Mod Envelope Hold", 0.0, 2000.0),
+            mod_envelope_decay: envelope_time_param("Mod Envelope Decay", 300.0, 5000.0),
+            mod_envelope_decay_tension: envelope_tension_param("Mod Envelope Decay Tension", -1.0),
+            mod_envelope_sustain: FloatParam::new(
+                "Mod Envelope Sustain",
+                0.0,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            ),
+            mod_envelope_release: envelope_time_param("Mod Envelope Release", 200.0, 5000.0),
+            mod_envelope_release_tension: envelope_tension_param(
+                "Mod Envelope Release Tension",
+                -1.0,
+            ),
+            mod_envelope_legato: BoolParam::new("Mod Envelope Legato", false),
+            mod_envelope_pitch: FloatParam::new(
+                "Mod Envelope Pitch",
+                0.0,
+                FloatRange::Linear {
+                    min: -24.0,
+                    max: 24.0,
+                },
+            )
+            .with_unit(" st"),
+            mod_envelope_fold: FloatParam::new(
+                "Mod Envelope Fold",
+                0.0,
+                FloatRange::Linear {
+                    min: -1.0,
+                    max: 1.0,
+                },
+            ),
+
+            bell_hum_tuning: bell_tuning_param("Bell Hum Tuning"),
+            bell_hum_level: bell_level_param("Bell Hum Level"),
+            bell_prime_tuning: bell_tuning_param("Bell Prime Tuning"),
+            bell_prime_level: bell_level_param("Bell Prime Level"),
+            bell_tierce_tuning: bell_tuning_param("Bell Tierce Tuning"),
+            bell_tierce_level: bell_level_param("Bell Tierce Level"),
+            bell_quint_tuning: bell_tuning_param("Bell Quint Tuning"),
+            bell_quint_level: bell_level_param("Bell Quint Level"),
+            bell_nominal_tuning: bell_tuning_param("Bell Nominal Tuning"),
+            bell_nominal_level: bell_level_param("Bell Nominal Level"),
+            bell_upper_tuning: bell_tuning_param("Bell Upper Partials Tuning"),
+            bell_upper_level: bell_level_param("Bell Upper Partials Level"),
+
+            bell_warble: FloatParam::new(
+                "Bell Warble",
+                0.8,
+                FloatRange::Linear { min: 0.0, max: 5.0 },
+            )
+            .with_unit(" Hz"),
+
+            bell_third: EnumParam::new("Bell Third", BellThird::Minor),
+
+            material_enabled: BoolParam::new("Material Damping", false),
+
+            material: FloatParam::new("Material", 0.0, FloatRange::Linear { min: 0.0, max: 1.0 }),
+
+            size: FloatParam::new("Size", 1.0, FloatRange::Linear { min: 0.5, max: 2.0 })
+                .with_unit(" x"),
+
+            tube_enabled: BoolParam::new("Tube", false),
+
+            tube_level: FloatParam::new(
+                "Tube Level",
+                0.5,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            ),
+
+            tube_detune: FloatParam::new(
+                "Tube Detune",
+                0.0,
+                FloatRange::Linear {
+                    min: -100.0,
+                    max: 100.0,
+                },
+            )
+            .with_unit(" cents"),
+
+            tube_damping: FloatParam::new(
+                "Tube Damping",
+                0.5,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            ),
+
+            buzz_enabled: BoolParam::new("Buzz", false),
+
+            buzz_threshold: FloatParam::new(
+                "Buzz Threshold",
+                0.05,
+                FloatRange::Skewed {
+                    min: 0.001,
+                    max: 0.5,
+                    factor: FloatRange::skew_factor(-2.0),
+                },
+            ),
+
+            buzz_level: FloatParam::new(
+                "Buzz Level",
+                0.5,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            ),
+
+            buzz_colour: FloatParam::new(
+                "Buzz Colour",
+                0.5,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            ),
+
+            audio_input_enabled: BoolParam::new("Audio Input", false),
+
+            input_gain: FloatParam::new(
+                "Input Gain",
+                util::db_to_gain(-12.0),
+                FloatRange::Linear {
+                    min: util::db_to_gain(-36.0),
+                    max: util::db_to_gain(12.0),
+                },
+            )
+            .with_smoother(SmoothingStyle::Logarithmic(5.0))
+            .with_unit(" dB")
+            .with_value_to_string(formatters::v2s_f32_gain_to_db(2))
+            .with_string_to_value(formatters::s2v_f32_gain_to_db()),
+
+            dry_wet: FloatParam::new("Dry/Wet", 1.0, FloatRange::Linear { min: 0.0, max: 1.0 }),
+
+            onset_enabled: BoolParam::new("Onset Trigger", false),
+
+            onset_sensitivity: FloatParam::new(
+                "Onset Sensitivity",
+                0.5,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            ),
+
+            onset_retrigger_time: FloatParam::new(
+                "Onset Retrigger Time",
+                80.0,
+                FloatRange::Skewed {
+                    min: 10.0,
+                    max: 1000.0,
+                    factor: FloatRange::skew_factor(-1.0),
+                },
+            )
+            .with_unit(" ms"),
+
+            onset_pitch_mode: EnumParam::new("Onset Pitch Mode", OnsetPitchMode::Cycle),
+
+            onset_note_count: IntParam::new(
+                "Onset Note Count",
+                1,
+                IntRange::Linear { min: 1, max: 4 },
+            ),
+
+            onset_note_1: onset_note_param("Onset Note 1", 60),
+            onset_note_2: onset_note_param("Onset Note 2", 64),
+            onset_note_3: onset_note_param("Onset Note 3", 67),
+            onset_note_4: onset_note_param("Onset Note 4", 72),
+
             wave_folder_enabled: BoolParam::new("Wave Folder", false),
 
             wave_folder_amount: FloatParam::new(
@@ -184,6 +1217,30 @@ impl Default for PockyplockyParams {
                 FloatRange::Linear { min: 1.0, max: 5.0 },
             ),
 
+            click_level: FloatParam::new(
+                "Click Level",
+                0.0,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            ),
+
+            click_tone: FloatParam::new(
+                "Click Tone",
+                0.5,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            ),
+
+            transient_attack: FloatParam::new(
+                "Transient Attack",
+                0.0,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            ),
+
+            transient_sustain: FloatParam::new(
+                "Transient Sustain",
+                0.0,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            ),
+
             second_voice_enabled: BoolParam::new("Second Voice", false),
 
             second_voice_detune: FloatParam::new(
@@ -209,6 +1266,7 @@ pub struct ParamBuffers {
     params: Arc<PockyplockyParams>,
     gain_buffer: [f32; MAX_BLOCK_SIZE],
     noise_level_buffer: [f32; MAX_BLOCK_SIZE],
+    input_gain_buffer: [f32; MAX_BLOCK_SIZE],
 }
 
 impl ParamBuffers {
@@ -217,6 +1275,7 @@ impl ParamBuffers {
             params,
             gain_buffer: [0.0; MAX_BLOCK_SIZE],
             noise_level_buffer: [0.0; MAX_BLOCK_SIZE],
+            input_gain_buffer: [0.0; MAX_BLOCK_SIZE],
         }
     }
 
@@ -230,6 +1289,11 @@ impl ParamBuffers {
             .breath_level
             .smoothed
             .next_block(&mut self.noise_level_buffer[..block_len], block_len);
+
+        self.params
+            .input_gain
+            .smoothed
+            .next_block(&mut self.input_gain_buffer[..block_len], block_len);
     }
 
     pub fn get_gain_buffer(&self) -> &[f32] {
@@ -239,4 +1303,8 @@ impl ParamBuffers {
     pub fn get_noise_level_buffer(&self) -> &[f32] {
         &self.noise_level_buffer
     }
+
+    pub fn get_input_gain_buffer(&self) -> &[f32] {
+        &self.input_gain_buffer
+    }
 }
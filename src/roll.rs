@@ -0,0 +1,113 @@
+use rand::Rng;
+use rand_pcg::Pcg32;
+use std::sync::Arc;
+
+use crate::{constants::DEFAULT_SAMPLE_RATE, params::PockyplockyParams};
+
+// Used when tempo sync is on but the host doesn't report a tempo
+const FALLBACK_TEMPO: f32 = 120.0;
+
+// At full humanize the time between strikes varies by this fraction, and the velocity by this much
+const MAX_TIMING_JITTER: f32 = 0.25;
+const MAX_VELOCITY_JITTER: f32 = 0.1;
+
+/// Keeps re-striking a held note, like a marimba or timpani roll. The two hands alternate, with the
+/// second hand playing a bit softer depending on the accent. Poly pressure takes over from the note
+/// velocity, so a roll can swell while it's being held.
+pub struct Roll {
+    params: Arc<PockyplockyParams>,
+    sample_rate: f32,
+    tempo: Option<f32>,
+    active: bool,
+    velocity: f32,
+    countdown: f32, // Samples until the next strike
+    second_hand: bool,
+    prng: Pcg32,
+}
+
+impl Roll {
+    pub fn new(params: Arc<PockyplockyParams>) -> Self {
+        Self {
+            params,
+            sample_rate: DEFAULT_SAMPLE_RATE,
+            tempo: None,
+            active: false,
+            velocity: 0.0,
+            countdown: 0.0,
+            second_hand: false,
+            prng: Pcg32::new(24680, 13579),
+        }
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+    }
+
+    pub fn set_tempo(&mut self, tempo: Option<f32>) {
+        self.tempo = tempo;
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    /// Starts rolling after the first strike of the note.
+    pub fn start(&mut self, velocity: f32) {
+        self.active = true;
+        self.velocity = velocity;
+        self.second_hand = false;
+        self.countdown = self.next_interval();
+    }
+
+    pub fn stop(&mut self) {
+        self.active = false;
+    }
+
+    pub fn set_pressure(&mut self, pressure: f32) {
+        self.velocity = pressure;
+    }
+
+    fn next_interval(&mut self) -> f32 {
+        let rate = if self.params.roll_sync.value() {
+            let beats_per_second = self.tempo.unwrap_or(FALLBACK_TEMPO) / 60.0;
+            beats_per_second * self.params.roll_division.value().strikes_per_beat()
+        } else {
+            self.params.roll_rate.value()
+        };
+
+        let jitter = self.params.roll_humanize.value() * MAX_TIMING_JITTER;
+        self.sample_rate / rate * (1.0 + self.prng.gen_range(-jitter..=jitter))
+    }
+
+    /// Advances by one block, returns the velocity of the strike that falls in it if there is one.
+    /// Strikes happen at the start of the block, which is short enough not to be heard.
+    pub fn next_block(&mut self, block_len: usize) -> Option<f32> {
+        if !self.active {
+            return None;
+        }
+
+        self.countdown -= block_len as f32;
+        if self.countdown > 0.0 {
+            return None;
+        }
+
+        self.countdown += self.next_interval();
+        self.second_hand = !self.second_hand;
+
+        let accent = if self.second_hand {
+            1.0 - self.params.roll_accent.value()
+        } else {
+            1.0
+        };
+        let jitter = self.params.roll_humanize.value() * MAX_VELOCITY_JITTER;
+        let velocity = self.velocity * accent * (1.0 + self.prng.gen_range(-jitter..=jitter));
+        Some(velocity.clamp(0.0, 1.0))
+    }
+
+    pub fn reset(&mut self) {
+        self.active = false;
+        self.velocity = 0.0;
+        self.countdown = 0.0;
+        self.second_hand = false;
+    }
+}
@@ -0,0 +1,115 @@
+use nih_plug::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{params::PockyplockyParams, wav::read_wav};
+
+// Exciter samples are meant to be short transients, anything longer is cut off. This also keeps
+// the samples from bloating the plugin state.
+const MAX_SAMPLE_SECONDS: f32 = 2.0;
+
+#[derive(Serialize, Deserialize)]
+pub struct SampleLayer {
+    pub sample_rate: f32,
+    pub samples: Vec<f32>,
+}
+
+/// A set of samples used as exciter, one per velocity layer from soft to hard. A kit is a folder
+/// of WAV files in `~/.pockyplocky/exciters`, its layers are the files sorted by name. Projects
+/// know their kit by its folder name.
+#[derive(Serialize, Deserialize)]
+pub struct SampleKit {
+    pub name: String,
+    pub layers: Vec<SampleLayer>,
+}
+
+pub enum SampleKitTask {
+    // Index of the kit folder in alphabetical order
+    Load(usize),
+}
+
+fn kits_directory() -> Option<PathBuf> {
+    let home = std::env::var_os("HOME").or_else(|| std::env::var_os("USERPROFILE"))?;
+    Some(Path::new(&home).join(".pockyplocky").join("exciters"))
+}
+
+fn sorted_entries(directory: &Path, is_wanted: impl Fn(&Path) -> bool) -> Vec<PathBuf> {
+    let mut entries: Vec<PathBuf> = fs::read_dir(directory)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|path| is_wanted(path))
+                .collect()
+        })
+        .unwrap_or_default();
+    entries.sort();
+    entries
+}
+
+fn kit_folder(index: usize) -> Option<PathBuf> {
+    let kits = sorted_entries(&kits_directory()?, |path| path.is_dir());
+    kits.into_iter().nth(index)
+}
+
+fn load_kit(folder: &Path, name: String) -> Option<SampleKit> {
+    let files = sorted_entries(folder, |path| {
+        path.extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("wav"))
+    });
+
+    let mut layers = Vec::new();
+    for file in files {
+        match read_wav(&file) {
+            Ok((mut samples, sample_rate)) => {
+                samples.truncate((MAX_SAMPLE_SECONDS * sample_rate as f32) as usize);
+                layers.push(SampleLayer {
+                    sample_rate: sample_rate as f32,
+                    samples,
+                });
+            }
+            Err(error) => nih_warn!("Can't load exciter sample {}: {}", file.display(), error),
+        }
+    }
+
+    if layers.is_empty() {
+        return None;
+    }
+
+    Some(SampleKit { name, layers })
+}
+
+/// Runs on the background thread. When the kit can't be found, like when a project is opened on
+/// another computer, the kit that was stored with the project is kept. So is a kit with the same
+/// name.
+pub fn run_task(params: &PockyplockyParams, task: SampleKitTask) {
+    match task {
+        SampleKitTask::Load(index) => {
+            let Some(folder) = kit_folder(index) else {
+                return;
+            };
+            let Some(name) = folder
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+            else {
+                return;
+            };
+            let is_loaded = params
+                .sample_kit
+                .read()
+                .is_ok_and(|kit| kit.as_ref().is_some_and(|kit| kit.name == name));
+            if is_loaded {
+                return;
+            }
+
+            if let Some(kit) = load_kit(&folder, name) {
+                nih_log!("Loaded exciter sample kit {}", kit.name);
+                if let Ok(mut loaded_kit) = params.sample_kit.write() {
+                    *loaded_kit = Some(kit);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,68 @@
+use crate::constants::DEFAULT_SAMPLE_RATE;
+
+// Attack times of the fast and the slow envelope, in seconds. The attack of a note is where the
+// fast envelope is ahead of the slow one.
+const FAST_ATTACK_TIME: f32 = 0.001;
+const SLOW_ATTACK_TIME: f32 = 0.02;
+const RELEASE_TIME: f32 = 0.1;
+
+// Gain change at full Transient Attack and full Transient Sustain, in dB
+const MAX_ATTACK_BOOST_DB: f32 = 12.0;
+const MAX_SUSTAIN_TRIM_DB: f32 = 12.0;
+
+/// Boosts the attack of a note and turns down what comes after it, independent of how loud the
+/// note is.
+pub struct TransientShaper {
+    fast_attack: f32,
+    slow_attack: f32,
+    release: f32,
+    fast: f32,
+    slow: f32,
+}
+
+impl TransientShaper {
+    pub fn new() -> Self {
+        let mut shaper = Self {
+            fast_attack: 0.0,
+            slow_attack: 0.0,
+            release: 0.0,
+            fast: 0.0,
+            slow: 0.0,
+        };
+        shaper.set_sample_rate(DEFAULT_SAMPLE_RATE);
+        shaper
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.fast_attack = 1.0 - (-1.0 / (FAST_ATTACK_TIME * sample_rate)).exp();
+        self.slow_attack = 1.0 - (-1.0 / (SLOW_ATTACK_TIME * sample_rate)).exp();
+        self.release = 1.0 - (-1.0 / (RELEASE_TIME * sample_rate)).exp();
+    }
+
+    pub fn reset(&mut self) {
+        self.fast = 0.0;
+        self.slow = 0.0;
+    }
+
+    /// Returns the gain for the next sample, given its level. `attack` and `sustain` go from 0.0 to
+    /// 1.0.
+    pub fn process(&mut self, level: f32, attack: f32, sustain: f32) -> f32 {
+        self.fast = follow(self.fast, level, self.fast_attack, self.release);
+        self.slow = follow(self.slow, level, self.slow_attack, self.release);
+
+        // Goes from 0.0 while the level is steady or falling to 1.0 at the start of a hit
+        let transient = if self.fast > 1e-9 {
+            (1.0 - self.slow / self.fast).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        let gain_db = attack * MAX_ATTACK_BOOST_DB * transient
+            - sustain * MAX_SUSTAIN_TRIM_DB * (1.0 - transient);
+        (gain_db * (std::f32::consts::LN_10 / 20.0)).exp()
+    }
+}
+
+fn follow(envelope: f32, level: f32, attack: f32, release: f32) -> f32 {
+    let coefficient = if level > envelope { attack } else { release };
+    envelope + (level - envelope) * coefficient
+}
@@ -3,11 +3,24 @@ use std::sync::Arc;
 use nih_plug::prelude::*;
 
 use crate::{
+    articulation::Articulation,
     constants::{DEFAULT_SAMPLE_RATE, MAX_BLOCK_SIZE},
     modal_synth::ModalSynth,
-    params::{ParamBuffers, PockyplockyParams},
+    params::{BreathMode, BreathSource, ParamBuffers, ParticleGesture, PockyplockyParams},
+    roll::Roll,
+    transient_shaper::TransientShaper,
 };
 
+// Level below which a released voice is considered silent in audio input mode, -80 dB
+const SILENCE_THRESHOLD: f32 = 1e-4;
+
+// Release time constant of the output envelope follower in seconds
+const ENVELOPE_RELEASE_TIME: f32 = 0.05;
+
+fn envelope_release(sample_rate: f32) -> f32 {
+    (-1.0 / (ENVELOPE_RELEASE_TIME * sample_rate)).exp()
+}
+
 pub struct Voice {
     params: Arc<PockyplockyParams>,
     pub active: bool,
@@ -18,12 +31,19 @@ pub struct Voice {
     pub sample_rate: f32,
     pub total_duration: usize, // Total duration based on longest mode decay time
     pub sample_count: usize,   // Current sample count since start
+    ring_duration: usize,      // Time the voice keeps sounding after a strike
+    held: bool,
+    envelope: f32, // Follows the output level, used to end voices in audio input mode
+    envelope_release: f32,
     pub modal_synth: ModalSynth,
     pub modal_synth2: ModalSynth,
+    roll: Roll,
+    transient_shaper: TransientShaper,
 }
 
 impl Voice {
-    pub fn new(params: Arc<PockyplockyParams>) -> Self {
+    /// `index` seeds the noise of the voice, the left and the right side get their own seeds.
+    pub fn new(params: Arc<PockyplockyParams>, index: usize) -> Self {
         Self {
             params: params.clone(),
             active: false,
@@ -34,8 +54,14 @@ impl Voice {
             sample_rate: DEFAULT_SAMPLE_RATE,
             total_duration: 0,
             sample_count: 0,
-            modal_synth: ModalSynth::new(params.clone()),
-            modal_synth2: ModalSynth::new(params),
+            ring_duration: 0,
+            held: false,
+            envelope: 0.0,
+            envelope_release: envelope_release(DEFAULT_SAMPLE_RATE),
+            modal_synth: ModalSynth::new(params.clone(), index as u64 * 2),
+            modal_synth2: ModalSynth::new(params.clone(), index as u64 * 2 + 1),
+            roll: Roll::new(params),
+            transient_shaper: TransientShaper::new(),
         }
     }
 
@@ -43,6 +69,9 @@ impl Voice {
         self.sample_rate = sample_rate;
         self.modal_synth.set_sample_rate(sample_rate);
         self.modal_synth2.set_sample_rate(sample_rate);
+        self.roll.set_sample_rate(sample_rate);
+        self.transient_shaper.set_sample_rate(sample_rate);
+        self.envelope_release = envelope_release(sample_rate);
     }
 
     pub fn start(
@@ -52,6 +81,7 @@ impl Voice {
         note: u8,
         internal_voice_id: u64,
         velocity: f32,
+        articulation: Articulation,
     ) {
         self.voice_id = voice_id;
         self.channel = channel;
@@ -66,26 +96,103 @@ impl Voice {
         let detune_factor1 = 1.0 - detune * 0.01;
         let detune_factor2 = 1.0 + detune * 0.01;
 
-        let max_decay_time = self
-            .modal_synth
-            .start(frequency * detune_factor1, velocity, decay);
+        let max_decay_time = self.modal_synth.start(
+            frequency * detune_factor1,
+            velocity,
+            decay,
+            note,
+            articulation,
+        );
 
         if self.params.second_voice_enabled.value() {
-            self.modal_synth2
-                .start(frequency * detune_factor2, velocity, decay);
+            self.modal_synth2.start(
+                frequency * detune_factor2,
+                velocity,
+                decay,
+                note,
+                articulation,
+            );
         }
 
         let mut total_decay_time = max_decay_time;
 
         // If noise level is above 0, include breath envelope time
-        if self.params.breath_level.value() > 0.0 {
+        if self.params.breath_level.value() > 0.0
+            && self.params.breath_mode.value() == BreathMode::OneShot
+        {
             let breath_attack_time = self.params.breath_attack.value() * 0.001;
             let breath_decay_time = self.params.breath_decay.value() * 0.001;
             total_decay_time = total_decay_time + breath_attack_time + breath_decay_time;
         }
 
         self.total_duration = (self.sample_rate * total_decay_time) as usize;
+        self.ring_duration = self.total_duration;
         self.active = true;
+        self.held = true;
+        self.envelope = 0.0;
+        self.transient_shaper.reset();
+
+        if self.params.roll_enabled.value() || articulation == Articulation::Roll {
+            self.roll.start(velocity);
+        } else {
+            self.roll.stop();
+        }
+    }
+
+    /// Called on note off, stops the roll, lifts the bow and stops a sustained breath. Single
+    /// strikes keep ringing.
+    pub fn release(&mut self) {
+        self.held = false;
+        self.roll.stop();
+        self.modal_synth.release();
+        self.modal_synth2.release();
+
+        // A bowed, scraped or blown note rings out from the moment the key is released
+        if self.sustains_while_held() {
+            self.total_duration = self.sample_count + self.ring_duration;
+        }
+    }
+
+    /// Whether the exciter keeps going for as long as the note is held.
+    fn sustains_while_held(&self) -> bool {
+        self.params.bow_enabled.value()
+            || self.params.jet_enabled.value()
+            || self.params.feedback_enabled.value()
+            || (self.params.particles_enabled.value()
+                && self.params.particle_gesture.value() == ParticleGesture::Scrape)
+            || (self.params.breath_level.value() > 0.0
+                && self.params.breath_mode.value() == BreathMode::Sustained)
+    }
+
+    /// Shakes the particles of a ringing note again.
+    pub fn shake(&mut self, strength: f32) {
+        self.modal_synth.shake(strength);
+        self.modal_synth2.shake(strength);
+        self.total_duration = self
+            .total_duration
+            .max(self.sample_count + self.ring_duration);
+    }
+
+    pub fn set_pressure(&mut self, pressure: f32) {
+        self.roll.set_pressure(pressure);
+        self.set_expression(pressure);
+        if self.params.breath_source.value() == BreathSource::PolyPressure {
+            self.set_breath(pressure);
+        }
+    }
+
+    pub fn set_expression(&mut self, expression: f32) {
+        self.modal_synth.set_expression(expression);
+        self.modal_synth2.set_expression(expression);
+    }
+
+    pub fn set_breath(&mut self, breath: f32) {
+        self.modal_synth.set_breath(breath);
+        self.modal_synth2.set_breath(breath);
+    }
+
+    pub fn set_tempo(&mut self, tempo: Option<f32>) {
+        self.roll.set_tempo(tempo);
     }
 
     #[allow(clippy::needless_range_loop)]
@@ -94,12 +201,39 @@ impl Voice {
         block_start: usize,
         block_len: usize,
         param_buffers: &ParamBuffers,
+        input: Option<&[f32]>,
         output: &mut [&mut [f32]],
     ) {
         let mut buffer = [0.0; MAX_BLOCK_SIZE];
 
+        // Every strike of a roll hits the resonators while they're still ringing
+        if let Some(velocity) = self.roll.next_block(block_len) {
+            self.modal_synth.strike(velocity);
+            if self.params.second_voice_enabled.value() {
+                self.modal_synth2.strike(velocity);
+            }
+            self.total_duration = self.sample_count + self.ring_duration;
+        }
+
+        // Only held notes are excited by the audio input, released ones ring out and end
+        let input = input.filter(|_| self.held);
         self.modal_synth
-            .process_block(&mut buffer, block_len, param_buffers);
+            .process_block(&mut buffer, block_len, param_buffers, input);
+        // The second voice is about as loud, following one of them is enough
+        self.follow_envelope(&buffer[..block_len]);
+
+        let mut shaper_gains = [1.0; MAX_BLOCK_SIZE];
+        let transient_attack = self.params.transient_attack.value();
+        let transient_sustain = self.params.transient_sustain.value();
+        if transient_attack > 0.0 || transient_sustain > 0.0 {
+            for i in 0..block_len {
+                shaper_gains[i] = self.transient_shaper.process(
+                    buffer[i].abs(),
+                    transient_attack,
+                    transient_sustain,
+                );
+            }
+        }
 
         if self.params.second_voice_enabled.value() {
             let stereo_spread = self.params.second_voice_stereo_spread.value();
@@ -107,28 +241,44 @@ impl Voice {
             let right_gain = 0.5 + stereo_spread * 0.5;
 
             for i in 0..block_len {
-                output[0][block_start + i] += buffer[i] * left_gain;
-                output[1][block_start + i] += buffer[i] * right_gain;
+                output[0][block_start + i] += buffer[i] * shaper_gains[i] * left_gain;
+                output[1][block_start + i] += buffer[i] * shaper_gains[i] * right_gain;
             }
 
             self.modal_synth2
-                .process_block(&mut buffer, block_len, param_buffers);
+                .process_block(&mut buffer, block_len, param_buffers, input);
 
             for i in 0..block_len {
-                output[0][block_start + i] += buffer[i] * right_gain;
-                output[1][block_start + i] += buffer[i] * left_gain;
+                output[0][block_start + i] += buffer[i] * shaper_gains[i] * right_gain;
+                output[1][block_start + i] += buffer[i] * shaper_gains[i] * left_gain;
             }
         } else {
             for i in 0..block_len {
-                output[0][block_start + i] += buffer[i];
-                output[1][block_start + i] += buffer[i];
+                output[0][block_start + i] += buffer[i] * shaper_gains[i];
+                output[1][block_start + i] += buffer[i] * shaper_gains[i];
             }
         }
 
         self.sample_count += block_len;
     }
 
+    fn follow_envelope(&mut self, buffer: &[f32]) {
+        for sample in buffer {
+            self.envelope = sample.abs().max(self.envelope * self.envelope_release);
+        }
+    }
+
     pub fn is_finished(&self) -> bool {
+        if self.roll.is_active() || (self.held && self.sustains_while_held()) {
+            return false;
+        }
+
+        // Audio input can keep exciting the voice for as long as the note is held, so it only ends
+        // once it has been released and has gone quiet
+        if self.params.audio_input_enabled.value() {
+            return !self.held && self.envelope < SILENCE_THRESHOLD;
+        }
+
         self.sample_count >= self.total_duration
     }
 
@@ -140,7 +290,11 @@ impl Voice {
         self.total_duration = 0;
         self.sample_count = 0;
         self.active = false;
+        self.held = false;
+        self.envelope = 0.0;
         self.modal_synth.reset();
         self.modal_synth2.reset();
+        self.roll.reset();
+        self.transient_shaper.reset();
     }
 }
@@ -1,20 +1,27 @@
 use std::sync::Arc;
 
-use crate::{params::PockyplockyParams, voice::Voice};
+use crate::{articulation::Articulation, params::PockyplockyParams, voice::Voice};
 use nih_plug::prelude::*;
 
 pub const NUM_VOICES: usize = 16;
+const NUM_CHANNELS: usize = 16;
 
 pub struct VoiceManager {
     voices: [Voice; NUM_VOICES],
     next_internal_voice_id: u64,
+    articulations: [Articulation; NUM_CHANNELS],
+    expressions: [f32; NUM_CHANNELS],
+    breaths: [f32; NUM_CHANNELS],
 }
 
 impl VoiceManager {
     pub fn new(params: Arc<PockyplockyParams>) -> Self {
         Self {
-            voices: std::array::from_fn(|_| Voice::new(params.clone())),
+            voices: std::array::from_fn(|i| Voice::new(params.clone(), i)),
             next_internal_voice_id: 0,
+            articulations: [Articulation::Normal; NUM_CHANNELS],
+            expressions: [1.0; NUM_CHANNELS],
+            breaths: [0.0; NUM_CHANNELS],
         }
     }
 
@@ -165,9 +172,97 @@ impl VoiceManager {
         }
     }
 
+    /// The articulation that new notes on a channel are played with.
+    pub fn articulation(&self, channel: u8) -> Articulation {
+        self.articulations[channel as usize % NUM_CHANNELS]
+    }
+
+    pub fn set_articulation(&mut self, channel: u8, articulation: Articulation) {
+        self.articulations[channel as usize % NUM_CHANNELS] = articulation;
+    }
+
+    /// The expression of a channel, set with the mod wheel. New notes start with it.
+    pub fn expression(&self, channel: u8) -> f32 {
+        self.expressions[channel as usize % NUM_CHANNELS]
+    }
+
+    pub fn set_expression(&mut self, channel: u8, expression: f32) {
+        self.expressions[channel as usize % NUM_CHANNELS] = expression;
+        for voice in &mut self.voices {
+            if voice.active && voice.channel == channel {
+                voice.set_expression(expression);
+            }
+        }
+    }
+
+    /// The level of a sustained breath on a channel, set with the breath controller or aftertouch.
+    /// New notes start with it.
+    pub fn breath(&self, channel: u8) -> f32 {
+        self.breaths[channel as usize % NUM_CHANNELS]
+    }
+
+    pub fn set_breath(&mut self, channel: u8, breath: f32) {
+        self.breaths[channel as usize % NUM_CHANNELS] = breath;
+        for voice in &mut self.voices {
+            if voice.active && voice.channel == channel {
+                voice.set_breath(breath);
+            }
+        }
+    }
+
+    /// Shake the particles of all voices on a channel. Lifting the shaker back to 0 doesn't shake.
+    pub fn shake(&mut self, channel: u8, strength: f32) {
+        if strength <= 0.0 {
+            return;
+        }
+
+        for voice in &mut self.voices {
+            if voice.active && voice.channel == channel {
+                voice.shake(strength);
+            }
+        }
+    }
+
+    /// Release the voices playing a note, which stops their rolls. If `voice_id` is not provided,
+    /// then this will release all matching voices.
+    pub fn release_voices(&mut self, voice_id: Option<i32>, channel: u8, note: u8) {
+        for voice in self.matching_voices(voice_id, channel, note) {
+            voice.release();
+        }
+    }
+
+    /// Set the poly pressure of the voices playing a note.
+    pub fn set_pressure(&mut self, voice_id: Option<i32>, channel: u8, note: u8, pressure: f32) {
+        for voice in self.matching_voices(voice_id, channel, note) {
+            voice.set_pressure(pressure);
+        }
+    }
+
+    pub fn set_tempo(&mut self, tempo: Option<f32>) {
+        for voice in &mut self.voices {
+            voice.set_tempo(tempo);
+        }
+    }
+
+    fn matching_voices(
+        &mut self,
+        voice_id: Option<i32>,
+        channel: u8,
+        note: u8,
+    ) -> impl Iterator<Item = &mut Voice> {
+        self.voices.iter_mut().filter(move |voice| {
+            let matches_voice_id = voice_id == Some(voice.voice_id);
+            let matches_note = voice_id.is_none() && channel == voice.channel && note == voice.note;
+            voice.active && (matches_voice_id || matches_note)
+        })
+    }
+
     /// Reset the voice data to initial state
     pub fn reset(&mut self) {
         self.next_internal_voice_id = 0;
+        self.articulations = [Articulation::Normal; NUM_CHANNELS];
+        self.expressions = [1.0; NUM_CHANNELS];
+        self.breaths = [0.0; NUM_CHANNELS];
         for v in &mut self.voices {
             v.reset();
         }
@@ -0,0 +1,106 @@
+use std::{
+    fs,
+    io::{Error, ErrorKind, Result},
+    path::Path,
+};
+
+const FORMAT_PCM: u16 = 1;
+const FORMAT_FLOAT: u16 = 3;
+const FORMAT_EXTENSIBLE: u16 = 0xFFFE;
+
+struct Format {
+    format: u16,
+    channels: usize,
+    sample_rate: u32,
+    bits_per_sample: u16,
+}
+
+fn invalid(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, message.to_string())
+}
+
+fn u16_at(bytes: &[u8], offset: usize) -> Result<u16> {
+    bytes
+        .get(offset..offset + 2)
+        .map(|b| u16::from_le_bytes([b[0], b[1]]))
+        .ok_or_else(|| invalid("unexpected end of file"))
+}
+
+fn u32_at(bytes: &[u8], offset: usize) -> Result<u32> {
+    bytes
+        .get(offset..offset + 4)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .ok_or_else(|| invalid("unexpected end of file"))
+}
+
+/// Reads a WAV file and mixes it down to mono. Returns the samples and their sample rate. Supports
+/// 8, 16, 24 and 32 bit integer and 32 and 64 bit float files.
+pub fn read_wav(path: &Path) -> Result<(Vec<f32>, u32)> {
+    let bytes = fs::read(path)?;
+    if bytes.get(0..4) != Some(b"RIFF") || bytes.get(8..12) != Some(b"WAVE") {
+        return Err(invalid("not a WAV file"));
+    }
+
+    let mut format = None;
+    let mut data = None;
+    let mut offset = 12;
+    while offset + 8 <= bytes.len() {
+        let id = &bytes[offset..offset + 4];
+        let size = u32_at(&bytes, offset + 4)? as usize;
+        let start = offset + 8;
+        let end = (start + size).min(bytes.len());
+
+        match id {
+            b"fmt " => {
+                let mut format_tag = u16_at(&bytes, start)?;
+                if format_tag == FORMAT_EXTENSIBLE {
+                    // The actual format is the first two bytes of the sub format GUID
+                    format_tag = u16_at(&bytes, start + 24)?;
+                }
+                format = Some(Format {
+                    format: format_tag,
+                    channels: u16_at(&bytes, start + 2)? as usize,
+                    sample_rate: u32_at(&bytes, start + 4)?,
+                    bits_per_sample: u16_at(&bytes, start + 14)?,
+                });
+            }
+            b"data" => data = Some(&bytes[start..end]),
+            _ => {}
+        }
+
+        // Chunks are padded to an even size
+        offset = start + size + (size & 1);
+    }
+
+    let format = format.ok_or_else(|| invalid("missing fmt chunk"))?;
+    let data = data.ok_or_else(|| invalid("missing data chunk"))?;
+    if format.channels == 0 {
+        return Err(invalid("no channels"));
+    }
+
+    let bytes_per_sample = (format.bits_per_sample / 8) as usize;
+    let decode: fn(&[u8]) -> f32 = match (format.format, format.bits_per_sample) {
+        (FORMAT_PCM, 8) => |b| (b[0] as f32 - 128.0) / 128.0,
+        (FORMAT_PCM, 16) => |b| i16::from_le_bytes([b[0], b[1]]) as f32 / 32768.0,
+        (FORMAT_PCM, 24) => |b| i32::from_le_bytes([0, b[0], b[1], b[2]]) as f32 / 2_147_483_648.0,
+        (FORMAT_PCM, 32) => {
+            |b| i32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f32 / 2_147_483_648.0
+        }
+        (FORMAT_FLOAT, 32) => |b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]),
+        (FORMAT_FLOAT, 64) => {
+            |b| f64::from_le_bytes([b[0], b[1], b[2], b[3], b[4], b[5], b[6], b[7]]) as f32
+        }
+        _ => return Err(invalid("unsupported sample format")),
+    };
+
+    let frame_size = bytes_per_sample * format.channels;
+    let samples = data
+        .chunks_exact(frame_size)
+        .map(|frame| {
+            let sum: f32 = frame.chunks_exact(bytes_per_sample).map(decode).sum();
+            sum / format.channels as f32
+        })
+        .collect();
+
+    Ok((samples, format.sample_rate))
+}